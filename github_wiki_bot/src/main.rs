@@ -188,10 +188,10 @@ impl StaticWikiBot {
             .map(|it| it.trim_start_matches("answer:").trim())
             .unwrap();
         let content = &body[content_start..];
-        self.acquire_lock_with_issue(&repo, locker_id).await;
+        self.acquire_lock_with_issue(repo, locker_id).await;
         let content = Self::update_file_contents(content, author);
         self.save_file_and_push(
-            &repo,
+            repo,
             "main",
             language,
             answer,
@@ -206,9 +206,9 @@ impl StaticWikiBot {
         )
         .unwrap();
         info!("File saved and pushed {}", title);
-        self.comment(&repo, id, "Merged. Thank you for contribution!")
+        self.comment(repo, id, "Merged. Thank you for contribution!")
             .await;
-        self.close_issue(&repo, id).await;
+        self.close_issue(repo, id).await;
         // we won't unlock here, build site action will do the unlock job
     }
 
@@ -218,7 +218,8 @@ impl StaticWikiBot {
             match current_holder {
                 None => {
                     info!("Seems no body is holding the lock, try to acquire it...");
-                    self.github_client
+                    let _ = self
+                        .github_client
                         .issues(&repo.owner, &repo.name)
                         .update(1)
                         .body(&format!("{}", locker_id))
@@ -229,7 +230,7 @@ impl StaticWikiBot {
                 Some(x) if x == locker_id => {
                     info!("Seems I got the lock successfully, waiting for any possible concurrent running locker...");
                     time::sleep(Duration::from_secs(10)).await;
-                    let current_holder_after_wait = self.current_lock_holder(&repo).await;
+                    let current_holder_after_wait = self.current_lock_holder(repo).await;
                     match current_holder_after_wait {
                         Some(x) if x == locker_id => {
                             info!("I still have the lock! Acquire success!");
@@ -254,7 +255,7 @@ impl StaticWikiBot {
             .await
             .unwrap()
             .body
-            .and_then(|holder| usize::from_str_radix(&holder, 10).ok())
+            .and_then(|holder| holder.parse().ok())
     }
 
     async fn handle_contribute_pr(&self, repo: &Repository, id: usize, locker_id: usize) {
@@ -272,8 +273,8 @@ impl StaticWikiBot {
             .iter()
             .all(|it| it.starts_with("a/data") && it.ends_with(".md"));
         if all_files_valid {
-            self.acquire_lock_with_issue(&repo, locker_id).await;
-            self.merge_pr(&repo, id).await;
+            self.acquire_lock_with_issue(repo, locker_id).await;
+            self.merge_pr(repo, id).await;
         } else {
             self.comment(
                 repo,
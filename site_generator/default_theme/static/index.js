@@ -0,0 +1,20 @@
+function isSingle(index) {
+    return index.hasOwnProperty("section")
+}
+
+window.addEventListener("load", () => {
+    for (const section of ["what", "how", "why"]) {
+        const title = $(`#do-you-know-${section}`);
+        if (title == null) {
+            continue;
+        }
+        const articles = site_index.filter(isSingle).filter(it => it.section == section);
+        if (articles.length == 0) {
+            title.closest(".do-you-know").remove();
+            continue;
+        }
+        const selected = articles[Math.floor(Math.random() * articles.length)];
+        title.innerHTML = selected.name;
+        $(`#do-you-know-${section}-button`).onclick = () => window.location.href = selected.url;
+    }
+});
\ No newline at end of file
@@ -0,0 +1,13 @@
+function isSingle(index) {
+    return index.hasOwnProperty("section")
+}
+
+window.addEventListener("load", () => {
+    $("#random-page").onclick = () => {
+        let to_visit = site_index[Math.floor(Math.random() * site_index.length)];
+        while (!isSingle(to_visit)) {
+            to_visit = site_index[Math.floor(Math.random() * site_index.length)];
+        }
+        window.location.href = to_visit.url;
+    }
+});
\ No newline at end of file
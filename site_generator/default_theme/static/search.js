@@ -0,0 +1,73 @@
+function isSingle(index) {
+    return index.hasOwnProperty("section")
+}
+
+const searchOptions = {
+    keys: [{
+        name: 'name',
+        weight: 0.45
+    }, {
+        name: 'aliases',
+        weight: 0.4
+    }, {
+        name: 'summary',
+        weight: 0.1
+    }, {
+        name: 'tags',
+        weight: 0.05
+    }],
+}
+
+const fuse = new Fuse(site_index, searchOptions);
+
+window.addEventListener("load", () => {
+    $(".search-input").onkeyup = () => {
+        const to_search = $(".search-input").value;
+        $("#search-result").innerHTML = "";
+        if (to_search !== "") {
+            const result = fuse.search(to_search);
+            if (result.length === 0) {
+                $(".search-container").style.opacity = 0;
+                $(".search-container").style.visibility = "hidden";
+                $("#search-result").innerHTML = `<li class="MuiListItem-root jss27 MuiListItem-gutters">无结果</li>`;
+            } else {
+                let html_str = "";
+                for (const item of result) {
+                    if (isSingle(item.item)) {
+                        html_str += `
+                <a class="MuiButtonBase-root MuiListItem-root MuiListItem-gutters MuiListItem-button"
+                    tabindex="0" role="button" aria-disabled="false" href="${item.item.url}">
+                    <div class="MuiListItemText-root">
+                        <span class="MuiTypography-root MuiListItemText-primary MuiTypography-body1 MuiTypography-displayBlock">
+                            ${item.item.name}
+                        </span>
+                    </div>
+                    <span class="MuiTouchRipple-root"></span>
+                </a>
+                `
+                    } else {
+                        html_str += `
+                <a class="MuiButtonBase-root MuiListItem-root MuiListItem-gutters MuiListItem-button"
+                    tabindex="0" role="button" aria-disabled="false" href="${item.item.url}">
+                    <div class="MuiListItemText-root">
+                        <span class="MuiTypography-root MuiListItemText-primary MuiTypography-body1 MuiTypography-displayBlock">
+                            ${item.item.name}
+                        </span>
+                    </div>
+                    <span class="MuiTouchRipple-root"></span>
+                </a>
+                `
+                    }
+                }
+                $(".search-container").style.opacity = 1;
+                $(".search-container").style.visibility = "visible";
+                $("#search-result").innerHTML = html_str;
+            }
+        }
+    };
+    $(".jss1").onclick = () => {
+        $(".search-container").style.opacity = 0;
+        $(".search-container").style.visibility = "hidden";
+        $(".search-input").value = "";
+    }
+});
@@ -0,0 +1,10 @@
+window.addEventListener("load", () => {
+    for (const tr of $$(".table-row")) {
+        let tableRow = tr;
+        let aElement = tableRow.getElementsByTagName("a")[0];
+        let href = aElement.getAttribute("href");
+        tableRow.onclick = () => {
+            window.location.href = href;
+        };
+    }
+});
\ No newline at end of file
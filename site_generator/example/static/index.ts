@@ -5,6 +5,7 @@ interface SingleArticleSearchIndex {
     section: string,
     category: string,
     filename: string,
+    url: string,
     name: string,
     aliases: Array<string>,
     summary: string,
@@ -25,18 +26,18 @@ function isSingle(index: SearchIndex): index is SingleArticleSearchIndex {
 }
 
 window.addEventListener("load", () => {
-    const language = document.documentElement.lang.split("-")[0];
-    const what = site_index.filter(isSingle).filter(it => it.section == "what");
-    const how = site_index.filter(isSingle).filter(it => it.section == "how");
-    const why = site_index.filter(isSingle).filter(it => it.section == "why");
-    const selected_what = what[Math.floor(Math.random() * what.length)];
-    const selected_how = how[Math.floor(Math.random() * how.length)];
-    const selected_why = why[Math.floor(Math.random() * why.length)];
-    $("#do-you-know-what").innerHTML = selected_what.name;
-    let public_url = $("meta[name='public-url']").getAttribute("content");
-    $("#do-you-know-what-button").onclick = () => window.location.href = `${public_url}/${language}/${selected_what.section}/${selected_what.filename}.html`;
-    $("#do-you-know-how").innerHTML = selected_how.name;
-    $("#do-you-know-how-button").onclick = () => window.location.href = `${public_url}/${language}/${selected_how.section}/${selected_how.filename}.html`;
-    $("#do-you-know-why").innerHTML = selected_why.name;
-    $("#do-you-know-why-button").onclick = () => window.location.href = `${public_url}/${language}/${selected_why.section}/${selected_why.filename}.html`;
+    for (const section of ["what", "how", "why"]) {
+        const title = $(`#do-you-know-${section}`);
+        if (title == null) {
+            continue;
+        }
+        const articles = site_index.filter(isSingle).filter(it => it.section == section);
+        if (articles.length == 0) {
+            title.closest(".do-you-know").remove();
+            continue;
+        }
+        const selected = articles[Math.floor(Math.random() * articles.length)];
+        title.innerHTML = selected.name;
+        $(`#do-you-know-${section}-button`).onclick = () => window.location.href = selected.url;
+    }
 });
\ No newline at end of file
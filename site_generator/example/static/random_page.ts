@@ -5,6 +5,7 @@ interface SingleArticleSearchIndex {
     section: string,
     category: string,
     filename: string,
+    url: string,
     name: string,
     aliases: Array<string>,
     summary: string,
@@ -26,12 +27,10 @@ declare const site_index: Array<SearchIndex>;
 
 window.addEventListener("load", () => {
     $("#random-page").onclick = () => {
-        let public_url = $("meta[name='public-url']").getAttribute("content");
-        let language = document.documentElement.lang.split("-")[0];
         let to_visit = site_index[Math.floor(Math.random() * site_index.length)];
         while (!isSingle(to_visit)) {
             to_visit = site_index[Math.floor(Math.random() * site_index.length)];
         }
-        window.location.href = `${public_url}/${language}/${to_visit.section}/${to_visit.filename}.html`;
+        window.location.href = to_visit.url;
     }
 });
\ No newline at end of file
@@ -300,6 +300,7 @@ interface SingleArticleSearchIndex {
     section: string,
     category: string,
     filename: string,
+    url: string,
     name: string,
     aliases: Array<string>,
     summary: string,
@@ -308,6 +309,7 @@ interface SingleArticleSearchIndex {
 
 interface ArticleArraySearchIndex {
     name: string,
+    url: string,
     articles: Array<SingleArticleSearchIndex>
 }
 
@@ -338,8 +340,6 @@ const searchOptions = {
 const fuse = new Fuse(site_index, searchOptions);
 
 window.addEventListener("load", () => {
-    const public_url = $("meta[name='public-url']").getAttribute("content");
-    const language = document.documentElement.lang.split("-")[0];
     $(".search-input").onkeyup = () => {
         const to_search = $(".search-input").value;
         $("#search-result").innerHTML = "";
@@ -355,7 +355,7 @@ window.addEventListener("load", () => {
                     if (isSingle(item.item)) {
                         html_str += `
                 <a class="MuiButtonBase-root MuiListItem-root MuiListItem-gutters MuiListItem-button"
-                    tabindex="0" role="button" aria-disabled="false" href="${public_url}/${language}/${item.item.section}/${item.item.filename}.html">
+                    tabindex="0" role="button" aria-disabled="false" href="${item.item.url}">
                     <div class="MuiListItemText-root">
                         <span class="MuiTypography-root MuiListItemText-primary MuiTypography-body1 MuiTypography-displayBlock">
                             ${item.item.name}
@@ -367,7 +367,7 @@ window.addEventListener("load", () => {
                     } else {
                         html_str += `
                 <a class="MuiButtonBase-root MuiListItem-root MuiListItem-gutters MuiListItem-button"
-                    tabindex="0" role="button" aria-disabled="false" href="${public_url}/${language}/disambiguation/${item.item.name}.html">
+                    tabindex="0" role="button" aria-disabled="false" href="${item.item.url}">
                     <div class="MuiListItemText-root">
                         <span class="MuiTypography-root MuiListItemText-primary MuiTypography-body1 MuiTypography-displayBlock">
                             ${item.item.name}
@@ -0,0 +1,7 @@
+#!/usr/bin/env bash
+
+# Scripts of the default theme are embedded precompiled, so rendering with it needs no tsc.
+# They are the example scripts with TypeScript types erased, the rest of the code is kept as is.
+export WORKDIR=$(pwd)
+cargo run --release --manifest-path $WORKDIR/site_generator/script/strip-types/Cargo.toml --target-dir $WORKDIR/target -- \
+    $WORKDIR/site_generator/default_theme/static $WORKDIR/site_generator/example/static/*.ts
@@ -0,0 +1,58 @@
+//! Compile the scripts of the default theme by erasing TypeScript types.
+//!
+//! Usage: `strip-types <output folder> <file.ts>...`
+
+use std::{env, fs, path::Path, process};
+
+mod strip;
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let output = match args.next() {
+        Some(it) => it,
+        None => {
+            eprintln!("usage: strip-types <output folder> <file.ts>...");
+            process::exit(2);
+        }
+    };
+    let mut failed = false;
+    for input in args {
+        let path = Path::new(&input);
+        let source = fs::read_to_string(path).unwrap();
+        match strip::transpile(&source) {
+            Ok(js) => {
+                let name = path.with_extension("js");
+                fs::write(
+                    Path::new(&output).join(name.file_name().unwrap()),
+                    tidy(&js),
+                )
+                .unwrap();
+            }
+            Err(e) => {
+                eprintln!("{}: {}", input, e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// Drop the blank lines left by erased declarations at the start, and collapse runs of them.
+fn tidy(js: &str) -> String {
+    let mut result = String::with_capacity(js.len());
+    let mut blank = true;
+    for line in js.split_inclusive('\n') {
+        if line.trim().is_empty() && line.ends_with('\n') {
+            if !blank {
+                result.push('\n');
+            }
+            blank = true;
+        } else {
+            result += line;
+            blank = false;
+        }
+    }
+    result
+}
@@ -0,0 +1,328 @@
+use oxc_allocator::Allocator;
+use oxc_ast::ast::*;
+use oxc_ast_visit::{walk, Visit};
+use oxc_parser::Parser;
+use oxc_span::{GetSpan, SourceType, Span};
+
+/// Keywords which only exist in TypeScript in front of class members.
+const MODIFIERS: [&str; 5] = ["public", "private", "protected", "readonly", "override"];
+
+/// Turn TypeScript into JavaScript by erasing types, the rest of the code is kept as is.
+///
+/// Syntax with runtime semantics (enums, namespaces with values, parameter properties
+/// and `import =`) is not supported, use a TypeScript compiler for them.
+pub fn transpile(source: &str) -> Result<String, String> {
+    let allocator = Allocator::default();
+    let parsed = Parser::new(&allocator, source, SourceType::ts()).parse();
+    if let Some(e) = parsed.errors.first() {
+        return Err(match e.labels.as_ref().and_then(|it| it.first()) {
+            Some(label) => format!("line {}: {}", line(source, label.offset()), e.message),
+            None => e.message.to_string(),
+        });
+    }
+    let mut eraser = Eraser {
+        source,
+        erased: Vec::new(),
+        errors: Vec::new(),
+    };
+    eraser.visit_program(&parsed.program);
+    if let Some(e) = eraser.errors.into_iter().next() {
+        return Err(e);
+    }
+    let mut erased = eraser.erased;
+    erased.sort_unstable_by_key(|it| it.start);
+    let mut output = String::with_capacity(source.len());
+    let mut position = 0;
+    for span in erased {
+        let (start, end) = (span.start as usize, span.end as usize);
+        if start > position {
+            output += &source[position..start];
+        }
+        position = position.max(end);
+    }
+    output += &source[position..];
+    Ok(output)
+}
+
+fn line(source: &str, offset: usize) -> usize {
+    source[..offset].matches('\n').count() + 1
+}
+
+/// Collects the spans of TypeScript only syntax.
+struct Eraser<'s> {
+    source: &'s str,
+    erased: Vec<Span>,
+    errors: Vec<String>,
+}
+
+impl Eraser<'_> {
+    fn erase(&mut self, start: u32, end: u32) {
+        if start < end {
+            self.erased.push(Span::new(start, end));
+        }
+    }
+
+    /// Erase `span` and the comma following it.
+    fn erase_list_item(&mut self, span: Span) {
+        let rest = &self.source[span.end as usize..];
+        let trimmed = rest.trim_start();
+        let end = match trimmed.strip_prefix(',') {
+            Some(after) => self.source.len() - after.len(),
+            None => span.end as usize,
+        };
+        self.erase(span.start, end as u32);
+    }
+
+    /// Erase the first `marker` (`?` or `!`) between `start` and `end`.
+    fn erase_marker(&mut self, marker: char, start: u32, end: u32) {
+        if let Some(offset) = self.source[start as usize..end as usize].find(marker) {
+            let position = start + offset as u32;
+            self.erase(position, position + 1);
+        }
+    }
+
+    /// Erase TypeScript modifiers between `start` and `end`.
+    fn erase_modifiers(&mut self, start: u32, end: u32) {
+        let text = &self.source[start as usize..end as usize];
+        let mut offset = 0;
+        for word in text.split_inclusive(|c: char| !c.is_alphanumeric() && c != '_' && c != '$') {
+            let trimmed = word.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '_');
+            if MODIFIERS.contains(&trimmed) || trimmed == "abstract" || trimmed == "declare" {
+                let position = start + offset as u32;
+                self.erase(position, position + trimmed.len() as u32);
+            }
+            offset += word.len();
+        }
+    }
+
+    fn unsupported(&mut self, what: &str, span: Span) {
+        self.errors.push(format!(
+            "line {}: {} cannot be transpiled by erasing types",
+            line(self.source, span.start as usize),
+            what
+        ));
+    }
+
+    /// Whether `declaration` only exists in the type system, reporting those with runtime semantics.
+    fn is_type_only(&mut self, declaration: &Declaration) -> bool {
+        match declaration {
+            Declaration::VariableDeclaration(it) => it.declare,
+            Declaration::FunctionDeclaration(it) => it.declare || it.body.is_none(),
+            Declaration::ClassDeclaration(it) => it.declare,
+            Declaration::TSTypeAliasDeclaration(_)
+            | Declaration::TSInterfaceDeclaration(_)
+            | Declaration::TSGlobalDeclaration(_) => true,
+            Declaration::TSEnumDeclaration(it) => {
+                if !it.declare {
+                    self.unsupported("enum", it.span);
+                }
+                true
+            }
+            Declaration::TSModuleDeclaration(it) => {
+                if !it.declare && !self.is_type_only_module(it) {
+                    self.unsupported("namespace with values", it.span);
+                }
+                true
+            }
+            Declaration::TSImportEqualsDeclaration(it) => {
+                if !it.import_kind.is_type() {
+                    self.unsupported("`import =`", it.span);
+                }
+                true
+            }
+        }
+    }
+
+    fn is_type_only_module(&mut self, module: &TSModuleDeclaration) -> bool {
+        match &module.body {
+            None => true,
+            Some(TSModuleDeclarationBody::TSModuleDeclaration(it)) => self.is_type_only_module(it),
+            Some(TSModuleDeclarationBody::TSModuleBlock(it)) => {
+                it.body.iter().all(|statement| match statement {
+                    Statement::ExportNamedDeclaration(it) => {
+                        it.declaration.as_ref().is_some_and(|it| {
+                            it.is_type() || matches!(it, Declaration::TSModuleDeclaration(_))
+                        })
+                    }
+                    _ => statement.as_declaration().is_some_and(Declaration::is_type),
+                })
+            }
+        }
+    }
+}
+
+impl<'a> Visit<'a> for Eraser<'_> {
+    fn visit_statement(&mut self, it: &Statement<'a>) {
+        let type_only = match it {
+            Statement::ImportDeclaration(it) => it.import_kind.is_type(),
+            Statement::ExportNamedDeclaration(it) => {
+                it.export_kind.is_type()
+                    || it
+                        .declaration
+                        .as_ref()
+                        .is_some_and(|it| self.is_type_only(it))
+            }
+            Statement::ExportAllDeclaration(it) => it.export_kind.is_type(),
+            Statement::ExportDefaultDeclaration(it) => matches!(
+                it.declaration,
+                ExportDefaultDeclarationKind::TSInterfaceDeclaration(_)
+            ),
+            Statement::TSNamespaceExportDeclaration(_) => true,
+            Statement::TSExportAssignment(it) => {
+                self.unsupported("`export =`", it.span);
+                true
+            }
+            _ => it.as_declaration().is_some_and(|it| self.is_type_only(it)),
+        };
+        if type_only {
+            self.erase(it.span().start, it.span().end);
+        } else {
+            walk::walk_statement(self, it);
+        }
+    }
+
+    fn visit_import_specifier(&mut self, it: &ImportSpecifier<'a>) {
+        if it.import_kind.is_type() {
+            self.erase_list_item(it.span);
+        }
+    }
+
+    fn visit_export_specifier(&mut self, it: &ExportSpecifier<'a>) {
+        if it.export_kind.is_type() {
+            self.erase_list_item(it.span);
+        }
+    }
+
+    fn visit_ts_type_annotation(&mut self, it: &TSTypeAnnotation<'a>) {
+        self.erase(it.span.start, it.span.end);
+    }
+
+    fn visit_ts_type_parameter_declaration(&mut self, it: &TSTypeParameterDeclaration<'a>) {
+        self.erase(it.span.start, it.span.end);
+    }
+
+    fn visit_ts_type_parameter_instantiation(&mut self, it: &TSTypeParameterInstantiation<'a>) {
+        self.erase(it.span.start, it.span.end);
+    }
+
+    fn visit_ts_this_parameter(&mut self, it: &TSThisParameter<'a>) {
+        self.erase_list_item(it.span);
+    }
+
+    fn visit_ts_as_expression(&mut self, it: &TSAsExpression<'a>) {
+        self.erase(it.expression.span().end, it.span.end);
+        self.visit_expression(&it.expression);
+    }
+
+    fn visit_ts_satisfies_expression(&mut self, it: &TSSatisfiesExpression<'a>) {
+        self.erase(it.expression.span().end, it.span.end);
+        self.visit_expression(&it.expression);
+    }
+
+    fn visit_ts_non_null_expression(&mut self, it: &TSNonNullExpression<'a>) {
+        self.erase(it.expression.span().end, it.span.end);
+        self.visit_expression(&it.expression);
+    }
+
+    fn visit_ts_type_assertion(&mut self, it: &TSTypeAssertion<'a>) {
+        self.erase(it.span.start, it.expression.span().start);
+        self.visit_expression(&it.expression);
+    }
+
+    fn visit_arrow_function_expression(&mut self, it: &ArrowFunctionExpression<'a>) {
+        // A line break left before `=>` would be a syntax error
+        if let Some(return_type) = &it.return_type {
+            self.erase(it.params.span.end, return_type.span.end);
+        }
+        walk::walk_arrow_function_expression(self, it);
+    }
+
+    fn visit_variable_declarator(&mut self, it: &VariableDeclarator<'a>) {
+        if it.definite {
+            let end = it
+                .type_annotation
+                .as_ref()
+                .map_or(it.span.end, |it| it.span.start);
+            self.erase_marker('!', it.id.span().end, end);
+        }
+        walk::walk_variable_declarator(self, it);
+    }
+
+    fn visit_formal_parameter(&mut self, it: &FormalParameter<'a>) {
+        if it.accessibility.is_some() || it.readonly || it.r#override {
+            self.unsupported("parameter property", it.span);
+        }
+        if it.optional {
+            let end = it
+                .type_annotation
+                .as_ref()
+                .map_or(it.span.end, |it| it.span.start);
+            self.erase_marker('?', it.pattern.span().end, end);
+        }
+        walk::walk_formal_parameter(self, it);
+    }
+
+    fn visit_class(&mut self, it: &Class<'a>) {
+        if it.r#abstract {
+            let class = it.body.span.start.min(
+                self.source[it.span.start as usize..]
+                    .find("class")
+                    .map_or(it.span.start, |offset| it.span.start + offset as u32),
+            );
+            self.erase_modifiers(it.span.start, class);
+        }
+        if let (Some(first), Some(last)) = (it.implements.first(), it.implements.last()) {
+            let start = self.source[..first.span.start as usize]
+                .rfind("implements")
+                .map_or(first.span.start, |it| it as u32);
+            self.erase(start, last.span.end);
+        }
+        walk::walk_class(self, it);
+    }
+
+    fn visit_class_element(&mut self, it: &ClassElement<'a>) {
+        let type_only = match it {
+            ClassElement::TSIndexSignature(_) => true,
+            ClassElement::MethodDefinition(it) => {
+                it.r#type == MethodDefinitionType::TSAbstractMethodDefinition
+                    || it.value.body.is_none()
+            }
+            ClassElement::PropertyDefinition(it) => {
+                it.r#type == PropertyDefinitionType::TSAbstractPropertyDefinition || it.declare
+            }
+            ClassElement::AccessorProperty(it) => {
+                it.r#type == AccessorPropertyType::TSAbstractAccessorProperty
+            }
+            ClassElement::StaticBlock(_) => false,
+        };
+        if type_only {
+            self.erase(it.span().start, it.span().end);
+            return;
+        }
+        match it {
+            ClassElement::MethodDefinition(it) => {
+                self.erase_modifiers(it.span.start, it.key.span().start);
+                if it.optional {
+                    self.erase_marker('?', it.key.span().end, it.value.params.span.start);
+                }
+            }
+            ClassElement::PropertyDefinition(it) => {
+                self.erase_modifiers(it.span.start, it.key.span().start);
+                if it.optional || it.definite {
+                    let end = match (&it.type_annotation, &it.value) {
+                        (Some(annotation), _) => annotation.span.start,
+                        (None, Some(value)) => value.span().start,
+                        (None, None) => it.span.end,
+                    };
+                    let marker = if it.optional { '?' } else { '!' };
+                    self.erase_marker(marker, it.key.span().end, end);
+                }
+            }
+            ClassElement::AccessorProperty(it) => {
+                self.erase_modifiers(it.span.start, it.key.span().start);
+            }
+            _ => {}
+        }
+        walk::walk_class_element(self, it);
+    }
+}
@@ -49,4 +49,5 @@ mv /tmp/tipedia/data/en/what/CIStr.md $WORKDIR/site_generator/example/data/en/wh
 mv /tmp/tipedia/data/en/why/pd-on-etcd.md $WORKDIR/site_generator/example/data/en/why/
 mv /tmp/tipedia/data/en/about.md $WORKDIR/site_generator/example/data/en/
 mv /tmp/tipedia/data/en/translation.toml $WORKDIR/site_generator/example/data/en/
-mv /tmp/site.toml $WORKDIR/site_generator/example/data/site.toml
\ No newline at end of file
+mv /tmp/site.toml $WORKDIR/site_generator/example/data/site.toml
+cd $WORKDIR && bash site_generator/script/compile-default-theme.sh
@@ -0,0 +1,197 @@
+use pulldown_cmark::escape::escape_html;
+
+use crate::markdown::code_ranges;
+
+/// Callouts recognized in the GitHub `> [!NOTE]` syntax.
+const GITHUB_KINDS: [&str; 5] = ["note", "tip", "important", "warning", "caution"];
+
+/// Expand `> [!WARNING]` blockquotes and `:::warning` containers in `source` into `<aside>` blocks,
+/// the content between them is still parsed as markdown.
+///
+/// `label` gives the title shown for a kind of callout, unless `:::kind Title` sets one.
+pub fn expand(source: &str, label: &dyn Fn(&str) -> String) -> String {
+    let code = code_ranges(source);
+    let mut offset = 0;
+    // Each line with whether it is part of a code block
+    let lines: Vec<_> = source
+        .split_inclusive('\n')
+        .map(|line| {
+            let content = offset + line.len() - line.trim_start().len();
+            offset += line.len();
+            (line, code.iter().any(|it| it.contains(&content)))
+        })
+        .collect();
+    let mut output = String::with_capacity(source.len());
+    let mut index = 0;
+    while index < lines.len() {
+        let (line, in_code) = lines[index];
+        let trimmed = line.trim();
+        if in_code {
+            output += line;
+            index += 1;
+        } else if let Some(kind) = github_kind(trimmed) {
+            let end = index
+                + 1
+                + lines[index + 1..]
+                    .iter()
+                    .take_while(|(it, _)| it.trim_start().starts_with('>'))
+                    .count();
+            let content: String = lines[index + 1..end]
+                .iter()
+                .map(|(it, _)| {
+                    let it = it.trim_start().strip_prefix('>').unwrap();
+                    it.strip_prefix(' ').unwrap_or(it)
+                })
+                .collect();
+            output += &aside(&kind, &label(&kind), &expand(&content, label));
+            index = end;
+        } else if let Some((kind, title)) = container_start(trimmed) {
+            let mut depth = 1;
+            let end = lines[index + 1..].iter().position(|(it, in_code)| {
+                let it = it.trim();
+                if *in_code {
+                    return false;
+                } else if container_start(it).is_some() {
+                    depth += 1;
+                } else if it == ":::" {
+                    depth -= 1;
+                }
+                depth == 0
+            });
+            let (content_end, end) = match end {
+                Some(end) => (index + 1 + end, index + 2 + end),
+                None => (lines.len(), lines.len()),
+            };
+            let content: String = lines[index + 1..content_end]
+                .iter()
+                .map(|(it, _)| *it)
+                .collect();
+            let title = title.map_or_else(|| label(&kind), ToString::to_string);
+            output += &aside(&kind, &title, &expand(&content, label));
+            index = end;
+        } else {
+            output += line;
+            index += 1;
+        }
+    }
+    output
+}
+
+/// The default title of a kind of callout, eg. `Warning` for `warning`.
+pub fn default_label(kind: &str) -> String {
+    let mut chars = kind.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+fn github_kind(line: &str) -> Option<String> {
+    let kind = line
+        .strip_prefix('>')?
+        .trim()
+        .strip_prefix("[!")?
+        .strip_suffix(']')?
+        .to_lowercase();
+    if GITHUB_KINDS.contains(&kind.as_str()) {
+        Some(kind)
+    } else {
+        None
+    }
+}
+
+fn container_start(line: &str) -> Option<(String, Option<&str>)> {
+    let rest = line.strip_prefix(":::")?.trim_start();
+    let (kind, title) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    if kind.is_empty() || !kind.chars().all(|c| c.is_alphanumeric() || c == '-') {
+        return None;
+    }
+    let title = title.trim();
+    Some((
+        kind.to_lowercase(),
+        if title.is_empty() { None } else { Some(title) },
+    ))
+}
+
+fn aside(kind: &str, title: &str, content: &str) -> String {
+    let mut escaped_title = String::new();
+    escape_html(&mut escaped_title, title).unwrap();
+    format!(
+        "<aside class=\"admonition admonition-{}\">\n<p class=\"admonition-title\">{}</p>\n\n{}\n\n</aside>\n\n",
+        kind,
+        escaped_title,
+        content.trim_end()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(source: &str) -> String {
+        super::expand(source, &default_label)
+    }
+
+    #[test]
+    fn github_callout() {
+        assert_eq!(
+            expand("> [!WARNING]\n> Mind the gap.\n>\n> Really.\nAfter\n"),
+            "<aside class=\"admonition admonition-warning\">\n\
+             <p class=\"admonition-title\">Warning</p>\n\nMind the gap.\n\nReally.\n\n</aside>\n\nAfter\n"
+        );
+        // Unknown kinds are plain blockquotes
+        let source = "> [!FOO]\n> bar\n";
+        assert_eq!(expand(source), source);
+    }
+
+    #[test]
+    fn container() {
+        assert_eq!(
+            expand(":::tip Read <this>\nContent\n:::\nAfter\n"),
+            "<aside class=\"admonition admonition-tip\">\n\
+             <p class=\"admonition-title\">Read &lt;this&gt;</p>\n\nContent\n\n</aside>\n\nAfter\n"
+        );
+    }
+
+    #[test]
+    fn nested_containers() {
+        let output = expand(":::note\nOuter\n:::warning\nInner\n:::\nStill outer\n:::\nAfter\n");
+        assert_eq!(
+            output,
+            "<aside class=\"admonition admonition-note\">\n\
+             <p class=\"admonition-title\">Note</p>\n\nOuter\n\
+             <aside class=\"admonition admonition-warning\">\n\
+             <p class=\"admonition-title\">Warning</p>\n\nInner\n\n</aside>\n\n\
+             Still outer\n\n</aside>\n\nAfter\n"
+        );
+    }
+
+    #[test]
+    fn unclosed_container() {
+        assert_eq!(
+            expand(":::note\nTo the end\n"),
+            "<aside class=\"admonition admonition-note\">\n\
+             <p class=\"admonition-title\">Note</p>\n\nTo the end\n\n</aside>\n\n"
+        );
+        // A stray closing marker is left alone
+        assert_eq!(expand("Text\n:::\n"), "Text\n:::\n");
+    }
+
+    #[test]
+    fn code_is_not_expanded() {
+        let source = "```md\n:::note\n> [!TIP]\n:::\n```\n";
+        assert_eq!(expand(source), source);
+        let source = "Text\n\n    :::note\n    Indented code\n    :::\n";
+        assert_eq!(expand(source), source);
+    }
+
+    #[test]
+    fn code_in_container() {
+        assert_eq!(
+            expand(":::note\n```md\n:::\n```\nAfter the code\n:::\nAfter\n"),
+            "<aside class=\"admonition admonition-note\">\n\
+             <p class=\"admonition-title\">Note</p>\n\n```md\n:::\n```\nAfter the code\n\n</aside>\n\n\
+             After\n"
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use lightningcss::stylesheet::{MinifyOptions, ParserOptions, PrinterOptions, StyleSheet};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::config::default_true;
+
+/// The web app manifest, icons in it are pointed to fingerprinted files.
+const WEB_MANIFEST: &str = "manifest.json";
+
+fn default_typescript_command() -> String {
+    "tsc --target es5".to_string()
+}
+
+/// Configured in `[assets]` of `site.toml`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AssetConfig {
+    /// Command compiling the `.ts` file given as its last argument into a `.js` file next to it.
+    #[serde(default = "default_typescript_command")]
+    pub typescript_command: String,
+    /// Minify `.css` files, scripts are kept as is as minify-js output differs from run to run.
+    #[serde(default)]
+    pub minify: bool,
+    /// Put the hash of the content into file names, so they can be cached forever.
+    #[serde(default = "default_true")]
+    pub fingerprint: bool,
+}
+
+impl Default for AssetConfig {
+    fn default() -> Self {
+        Self {
+            typescript_command: default_typescript_command(),
+            minify: false,
+            fingerprint: true,
+        }
+    }
+}
+
+/// A file from the static folder.
+pub struct StaticFile {
+    /// Path relative to the static folder, by which templates refer to the file, eg. `index.js`.
+    pub name: String,
+    /// Path relative to the output root.
+    pub path: String,
+    pub content: Vec<u8>,
+}
+
+impl StaticFile {
+    fn extension(&self) -> Option<&str> {
+        self.path.rsplit_once('.').map(|(_, extension)| extension)
+    }
+
+    /// Replace the extension of both the name and the path.
+    fn set_extension(&mut self, extension: &str) {
+        for it in [&mut self.name, &mut self.path] {
+            if let Some((stem, _)) = it.rsplit_once('.') {
+                *it = format!("{}.{}", stem, extension);
+            }
+        }
+    }
+}
+
+/// One step static files go through before they are written to the output.
+pub trait AssetStage {
+    fn process(&self, file: &mut StaticFile) -> Result<(), String>;
+}
+
+/// Compile `.ts` files into `.js` ones with an external compiler.
+pub struct TypeScript {
+    command: String,
+}
+
+impl TypeScript {
+    fn compile(&self, file: &StaticFile) -> Result<Vec<u8>, String> {
+        let command = &self.command;
+        let mut args = command.split_whitespace();
+        let program = args.next().ok_or("`typescript_command` is empty")?;
+        // Removed with its content when dropped
+        let dir = tempfile::Builder::new()
+            .prefix("static-wiki-")
+            .tempdir()
+            .map_err(|e| format!("cannot create temporary folder: {}", e))?;
+        let file_name = Path::new(&file.name).file_name().unwrap();
+        let source = dir.path().join(file_name);
+        fs::write(&source, &file.content)
+            .map_err(|e| format!("cannot write {}: {}", source.display(), e))?;
+        let output = Command::new(program)
+            .args(args)
+            .arg(file_name)
+            .current_dir(dir.path())
+            .output()
+            .map_err(|e| {
+                format!(
+                    "cannot run `{}`: {}, install it or set `typescript_command` in `[assets]` of site.toml",
+                    command, e
+                )
+            })?;
+        let compiled = source.with_extension("js");
+        if output.status.success() {
+            fs::read(&compiled).map_err(|e| format!("cannot read {}: {}", compiled.display(), e))
+        } else {
+            Err(format!(
+                "`{}` failed: {}{}",
+                command,
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            ))
+        }
+    }
+}
+
+impl AssetStage for TypeScript {
+    fn process(&self, file: &mut StaticFile) -> Result<(), String> {
+        if file.extension() != Some("ts") {
+            return Ok(());
+        }
+        file.content = self.compile(file)?;
+        file.set_extension("js");
+        Ok(())
+    }
+}
+
+/// Minify `.css` files, those not getting smaller are kept as is.
+pub struct Minify;
+
+impl Minify {
+    fn minify_css(content: &[u8]) -> Result<Vec<u8>, String> {
+        let content = std::str::from_utf8(content).map_err(|e| e.to_string())?;
+        let mut stylesheet = StyleSheet::parse(content, ParserOptions::default())
+            .map_err(|e| format!("cannot minify: {}", e))?;
+        stylesheet
+            .minify(MinifyOptions::default())
+            .map_err(|e| format!("cannot minify: {}", e))?;
+        let printed = stylesheet
+            .to_css(PrinterOptions {
+                minify: true,
+                ..PrinterOptions::default()
+            })
+            .map_err(|e| format!("cannot minify: {}", e))?;
+        Ok(printed.code.into_bytes())
+    }
+}
+
+impl AssetStage for Minify {
+    fn process(&self, file: &mut StaticFile) -> Result<(), String> {
+        let minified = match file.extension() {
+            Some("css") => Self::minify_css(&file.content)?,
+            _ => return Ok(()),
+        };
+        if minified.len() < file.content.len() {
+            file.content = minified;
+        }
+        Ok(())
+    }
+}
+
+/// Put the hash of the content into file names, eg. `index.0123456789abcdef.js`.
+pub struct Fingerprint;
+
+impl AssetStage for Fingerprint {
+    fn process(&self, file: &mut StaticFile) -> Result<(), String> {
+        let hash = format!("{:x}", Sha256::digest(&file.content));
+        let (dir, file_name) = file.path.rsplit_once('/').unwrap_or(("", &file.path));
+        let file_name = match file_name.rsplit_once('.') {
+            Some((stem, extension)) => format!("{}.{}.{}", stem, &hash[..16], extension),
+            None => format!("{}.{}", file_name, &hash[..16]),
+        };
+        file.path = if dir.is_empty() {
+            file_name
+        } else {
+            format!("{}/{}", dir, file_name)
+        };
+        Ok(())
+    }
+}
+
+/// Copies the static folder into the output, passing every file through the stages.
+pub struct AssetPipeline {
+    stages: Vec<Box<dyn AssetStage>>,
+}
+
+impl AssetPipeline {
+    pub fn new(config: &AssetConfig) -> Self {
+        let mut pipeline = Self { stages: Vec::new() };
+        pipeline.add_stage(Box::new(TypeScript {
+            command: config.typescript_command.clone(),
+        }));
+        if config.minify {
+            pipeline.add_stage(Box::new(Minify));
+        }
+        if config.fingerprint {
+            pipeline.add_stage(Box::new(Fingerprint));
+        }
+        pipeline
+    }
+
+    /// Stages run in the order they are added.
+    pub fn add_stage(&mut self, stage: Box<dyn AssetStage>) {
+        self.stages.push(stage);
+    }
+
+    /// Process static files given by their names, type declarations (`.d.ts`) are left out.
+    pub fn process(&self, files: &BTreeMap<String, Vec<u8>>) -> Assets {
+        let mut processed: Vec<StaticFile> = Vec::new();
+        let mut error_count = 0;
+        let mut names: Vec<_> = files.keys().collect();
+        // Referring to the others, it goes last
+        names.sort_by_key(|it| *it == WEB_MANIFEST);
+        for name in names {
+            if name.ends_with(".d.ts") {
+                continue;
+            }
+            info!("process static file {} ...", name);
+            let mut file = StaticFile {
+                path: plain_path(name),
+                content: files[name].clone(),
+                name: name.clone(),
+            };
+            let result = if file.name == WEB_MANIFEST {
+                let paths = processed
+                    .iter()
+                    .map(|it| (plain_path(&it.name), it.path.clone()))
+                    .collect();
+                rewrite_web_manifest(&file.content, &paths).map(|it| file.content = it)
+            } else {
+                Ok(())
+            };
+            match result.and_then(|_| {
+                self.stages
+                    .iter()
+                    .try_for_each(|stage| stage.process(&mut file))
+            }) {
+                Ok(()) => processed.push(file),
+                Err(e) => {
+                    error!("static/{}: {}", file.name, e);
+                    error_count += 1;
+                }
+            }
+        }
+        if error_count != 0 {
+            panic!(
+                "{} error(s) found while processing static files",
+                error_count
+            );
+        }
+        Assets { files: processed }
+    }
+}
+
+/// All files under `dir`, sorted.
+pub fn files(dir: &Path) -> Vec<PathBuf> {
+    let mut result = Vec::new();
+    for entry in fs::read_dir(dir).unwrap().filter_map(|it| it.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            result.append(&mut files(&path));
+        } else {
+            result.push(path);
+        }
+    }
+    result.sort_unstable();
+    result
+}
+
+/// Content of all files under `dir` by their paths relative to it, empty if it does not exist.
+pub fn read_files(dir: &Path) -> BTreeMap<String, Vec<u8>> {
+    if !dir.is_dir() {
+        return BTreeMap::new();
+    }
+    files(dir)
+        .into_iter()
+        .map(|path| {
+            let name = path
+                .strip_prefix(dir)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .replace('\\', "/");
+            (name, fs::read(&path).unwrap())
+        })
+        .collect()
+}
+
+/// Path relative to the output root of the file named `name`, before it is fingerprinted.
+pub fn plain_path(name: &str) -> String {
+    // `start_url` in the web app manifest is relative to it, so it has to be in the root
+    if name == WEB_MANIFEST {
+        name.to_string()
+    } else {
+        format!("static/{}", name)
+    }
+}
+
+/// Point paths of static files in the web app manifest, like `static/logo192.png`, to the processed files.
+fn rewrite_web_manifest(
+    content: &[u8],
+    paths: &HashMap<String, String>,
+) -> Result<Vec<u8>, String> {
+    fn rewrite(value: &mut Value, paths: &HashMap<String, String>) {
+        match value {
+            Value::String(it) => {
+                let (slash, path) = match it.strip_prefix('/') {
+                    Some(path) => ("/", path),
+                    None => ("", it.as_str()),
+                };
+                if let Some(processed) = paths.get(path) {
+                    *it = format!("{}{}", slash, processed);
+                }
+            }
+            Value::Array(it) => it.iter_mut().for_each(|it| rewrite(it, paths)),
+            Value::Object(it) => it.values_mut().for_each(|it| rewrite(it, paths)),
+            _ => {}
+        }
+    }
+    let mut manifest: Value = serde_json::from_slice(content).map_err(|e| e.to_string())?;
+    rewrite(&mut manifest, paths);
+    Ok(serde_json::to_vec_pretty(&manifest).unwrap())
+}
+
+/// Processed static files.
+pub struct Assets {
+    files: Vec<StaticFile>,
+}
+
+impl Assets {
+    /// Path relative to the output root of each file, by name.
+    pub fn manifest(&self) -> BTreeMap<String, String> {
+        self.files
+            .iter()
+            .map(|it| (it.name.clone(), it.path.clone()))
+            .collect()
+    }
+
+    /// Write the files, and the manifest as `asset-manifest.json`.
+    pub fn write_to(&self, output: impl AsRef<Path>) {
+        for file in &self.files {
+            let path = output.as_ref().join(&file.path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, &file.content).unwrap();
+        }
+        fs::write(
+            output.as_ref().join("asset-manifest.json"),
+            serde_json::to_string_pretty(&self.manifest()).unwrap(),
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Default of options which are on unless turned off in `site.toml`.
+pub fn default_true() -> bool {
+    true
+}
+
+fn default_cache_dir() -> PathBuf {
+    PathBuf::from(".cache")
+}
+
+/// Configured in `[cache]` of `site.toml`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CacheConfig {
+    /// Rendered diagrams and processed images are kept here by content hash,
+    /// relative to the working directory.
+    #[serde(default = "default_cache_dir")]
+    pub dir: PathBuf,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            dir: default_cache_dir(),
+        }
+    }
+}
+
+impl CacheConfig {
+    /// Folder of one kind of cached files, eg. `diagrams`.
+    pub fn folder(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+}
@@ -0,0 +1,254 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Write},
+    path::Path,
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use layout::{
+    backends::svg::SVGWriter,
+    gv::{DotParser, GraphBuilder},
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::default_true;
+
+fn default_dot_command() -> String {
+    "dot".to_string()
+}
+
+fn default_mermaid_command() -> String {
+    "mmdc".to_string()
+}
+
+/// Configured in `[diagrams]` of `site.toml`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DiagramConfig {
+    /// Turn ```` ```dot ```` and ```` ```mermaid ```` blocks into inline svg.
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Graphviz executable, the built-in layout is used when it cannot be run.
+    #[serde(default = "default_dot_command")]
+    pub dot_command: String,
+    /// mermaid-cli executable, required by mermaid blocks.
+    #[serde(default = "default_mermaid_command")]
+    pub mermaid_command: String,
+    /// Set once a missing `mermaid_command` is reported, mermaid blocks are then left as code.
+    #[serde(skip)]
+    mermaid_missing: Arc<AtomicBool>,
+}
+
+impl Default for DiagramConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dot_command: default_dot_command(),
+            mermaid_command: default_mermaid_command(),
+            mermaid_missing: Arc::default(),
+        }
+    }
+}
+
+impl DiagramConfig {
+    /// Render a fenced block written in `language`, returns `None` if it is not a diagram.
+    ///
+    /// Rendered diagrams are cached in `cache_dir`.
+    pub fn render(
+        &self,
+        code: &str,
+        language: &str,
+        cache_dir: &Path,
+    ) -> Option<Result<String, String>> {
+        if !self.enabled
+            || !matches!(language, "dot" | "mermaid")
+            || language == "mermaid" && self.mermaid_missing.load(Ordering::Relaxed)
+        {
+            return None;
+        }
+        let mut hasher = Sha256::new();
+        hasher.update(language);
+        hasher.update([0]);
+        hasher.update(code);
+        let key = format!("{:x}", hasher.finalize());
+        let cached = cache_dir.join(format!("{}.svg", key));
+        if let Ok(svg) = fs::read_to_string(&cached) {
+            return Some(Ok(figure(language, &svg, &key)));
+        }
+        let svg = match language {
+            "dot" => self.render_dot(code),
+            _ => match self.render_mermaid(code, &key, cache_dir) {
+                Ok(svg) => Ok(svg),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    self.mermaid_missing.store(true, Ordering::Relaxed);
+                    Err(format!(
+                        "{} not found, install mermaid-cli or set `mermaid_command` in `[diagrams]` of site.toml",
+                        self.mermaid_command
+                    ))
+                }
+                Err(e) => Err(format!("cannot render mermaid diagram: {}", e)),
+            },
+        };
+        Some(svg.map(|svg| {
+            let svg = &svg[svg.find("<svg").unwrap_or(0)..];
+            if let Err(e) = fs::create_dir_all(cache_dir).and_then(|_| fs::write(&cached, svg)) {
+                warn!("cannot cache diagram {}: {}", cached.display(), e);
+            }
+            figure(language, svg, &key)
+        }))
+    }
+
+    fn render_dot(&self, code: &str) -> Result<String, String> {
+        match run_dot(&self.dot_command, code) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            result => return result.map_err(|e| format!("cannot render dot diagram: {}", e)),
+        }
+        let mut parser = DotParser::new(code);
+        let graph = parser
+            .process()
+            .map_err(|e| format!("cannot parse dot diagram: {}", e))?;
+        let mut builder = GraphBuilder::new();
+        builder.visit_graph(&graph);
+        let mut svg = SVGWriter::new();
+        builder.get().do_it(false, false, false, &mut svg);
+        Ok(svg.finalize())
+    }
+
+    fn render_mermaid(&self, code: &str, key: &str, cache_dir: &Path) -> io::Result<String> {
+        fs::create_dir_all(cache_dir)?;
+        let input = cache_dir.join(format!("{}.mmd", key));
+        let output = cache_dir.join(format!("{}.mmd.svg", key));
+        fs::write(&input, code)?;
+        let status = Command::new(&self.mermaid_command)
+            .arg("-i")
+            .arg(&input)
+            .arg("-o")
+            .arg(&output)
+            .stdout(Stdio::null())
+            .status();
+        fs::remove_file(&input)?;
+        if !status?.success() {
+            return Err(io::Error::other(format!("{} failed", self.mermaid_command)));
+        }
+        let svg = fs::read_to_string(&output)?;
+        fs::remove_file(&output)?;
+        Ok(svg)
+    }
+}
+
+fn run_dot(command: impl AsRef<Path>, code: &str) -> io::Result<String> {
+    let mut child = Command::new(command.as_ref())
+        .arg("-Tsvg")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(code.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Wrap a rendered diagram, ids in it are prefixed by its `key`.
+fn figure(language: &str, svg: &str, key: &str) -> String {
+    format!(
+        "<figure class=\"diagram diagram-{}\">{}</figure>\n",
+        language,
+        scope_ids(svg, &format!("diagram-{}", &key[..8]))
+    )
+}
+
+/// Prefix the ids defined in `svg` and references to them, as diagrams on the same page
+/// often use the same ids, eg. `endarrow`.
+fn scope_ids(svg: &str, prefix: &str) -> String {
+    let is_name = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+    let ids: HashSet<&str> = svg
+        .match_indices(" id=\"")
+        .map(|(at, it)| {
+            let rest = &svg[at + it.len()..];
+            &rest[..rest.find(|c| !is_name(c)).unwrap_or(rest.len())]
+        })
+        .collect();
+    let mut result = String::with_capacity(svg.len());
+    let mut rest = svg;
+    while let Some(at) = rest.find(['#', '"']) {
+        result += &rest[..=at];
+        rest = &rest[at + 1..];
+        let name = &rest[..rest.find(|c| !is_name(c)).unwrap_or(rest.len())];
+        if ids.contains(name) && (result.ends_with('#') || result.ends_with(" id=\"")) {
+            result += prefix;
+            result.push('-');
+        }
+    }
+    result += rest;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope_ids() {
+        let svg = "<svg id=\"my-svg\"><style>#my-svg .a{fill:#fff}</style>\
+                   <marker id=\"endarrow\"/><path marker-end=\"url(#endarrow)\"/>\
+                   <use href=\"#endarrow\"/><text>#endarrow-like</text></svg>";
+        assert_eq!(
+            super::scope_ids(svg, "d"),
+            "<svg id=\"d-my-svg\"><style>#d-my-svg .a{fill:#fff}</style>\
+             <marker id=\"d-endarrow\"/><path marker-end=\"url(#d-endarrow)\"/>\
+             <use href=\"#d-endarrow\"/><text>#endarrow-like</text></svg>"
+        );
+    }
+
+    #[test]
+    fn diagrams_do_not_share_ids() {
+        let config = DiagramConfig {
+            dot_command: "/nonexistent/dot".to_string(),
+            ..DiagramConfig::default()
+        };
+        let cache_dir = tempfile::tempdir().unwrap();
+        let render = |code| {
+            config
+                .render(code, "dot", cache_dir.path())
+                .unwrap()
+                .unwrap()
+        };
+        let a = render("digraph { a -> b }");
+        let b = render("digraph { c -> d }");
+        let ids = |svg: &str| -> HashSet<String> {
+            svg.split(" id=\"")
+                .skip(1)
+                .map(|it| it[..it.find('"').unwrap()].to_string())
+                .collect()
+        };
+        assert!(!ids(&a).is_empty());
+        assert!(ids(&a).is_disjoint(&ids(&b)));
+    }
+
+    #[test]
+    fn missing_mermaid_is_reported_once() {
+        let config = DiagramConfig {
+            mermaid_command: "/nonexistent/mmdc".to_string(),
+            ..DiagramConfig::default()
+        };
+        let cache_dir = tempfile::tempdir().unwrap();
+        assert!(matches!(
+            config.render("graph TD; a-->b", "mermaid", cache_dir.path()),
+            Some(Err(_))
+        ));
+        assert!(config
+            .render("graph TD; c-->d", "mermaid", cache_dir.path())
+            .is_none());
+    }
+}
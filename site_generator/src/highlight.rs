@@ -0,0 +1,96 @@
+use lazy_static::lazy_static;
+use pulldown_cmark::escape::escape_html;
+use serde::{Deserialize, Serialize};
+use syntect::{
+    highlighting::ThemeSet,
+    html::{
+        css_for_theme_with_class_style, highlighted_html_for_string, ClassStyle,
+        ClassedHTMLGenerator,
+    },
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+
+lazy_static! {
+    static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
+    static ref THEME_SET: ThemeSet = ThemeSet::load_defaults();
+}
+
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+/// Where the colors of highlighted code come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightMode {
+    /// Code blocks are left as is
+    None,
+    /// Tokens get css classes, colors are defined in the generated `highlight.css`
+    #[default]
+    Classes,
+    /// Tokens get inline `style` attributes
+    Inline,
+}
+
+fn default_theme() -> String {
+    "base16-ocean.dark".to_string()
+}
+
+/// Configured in `[highlight]` of `site.toml`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HighlightConfig {
+    #[serde(default)]
+    pub mode: HighlightMode,
+    /// One of the themes bundled with syntect, eg. `InspiredGitHub` or `Solarized (light)`.
+    #[serde(default = "default_theme")]
+    pub theme: String,
+}
+
+impl Default for HighlightConfig {
+    fn default() -> Self {
+        Self {
+            mode: HighlightMode::default(),
+            theme: default_theme(),
+        }
+    }
+}
+
+impl HighlightConfig {
+    /// Highlight `code` written in `language`, returns `None` if the language is unknown.
+    pub fn highlight(&self, code: &str, language: &str) -> Option<String> {
+        let syntax = SYNTAX_SET.find_syntax_by_token(language)?;
+        let mut language_class = String::new();
+        escape_html(&mut language_class, language).unwrap();
+        match self.mode {
+            HighlightMode::None => None,
+            HighlightMode::Classes => {
+                let mut generator =
+                    ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAX_SET, CLASS_STYLE);
+                for line in LinesWithEndings::from(code) {
+                    generator
+                        .parse_html_for_line_which_includes_newline(line)
+                        .ok()?;
+                }
+                Some(format!(
+                    "<pre class=\"highlight\"><code class=\"language-{}\">{}</code></pre>\n",
+                    language_class,
+                    generator.finalize()
+                ))
+            }
+            HighlightMode::Inline => {
+                highlighted_html_for_string(code, &SYNTAX_SET, syntax, self.theme()).ok()
+            }
+        }
+    }
+
+    /// Stylesheet for the `classes` mode.
+    pub fn stylesheet(&self) -> String {
+        css_for_theme_with_class_style(self.theme(), CLASS_STYLE).unwrap()
+    }
+
+    fn theme(&self) -> &'static syntect::highlighting::Theme {
+        THEME_SET
+            .themes
+            .get(&self.theme)
+            .unwrap_or_else(|| panic!("unknown highlight theme {}", self.theme))
+    }
+}
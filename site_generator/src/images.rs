@@ -0,0 +1,229 @@
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use image::{
+    codecs::{avif::AvifEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    DynamicImage, ImageFormat,
+};
+use serde::{Deserialize, Serialize};
+
+/// Encodings generated besides the original one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Webp,
+    Avif,
+}
+
+impl Encoding {
+    fn extension(self) -> &'static str {
+        match self {
+            Encoding::Webp => "webp",
+            Encoding::Avif => "avif",
+        }
+    }
+
+    fn mime(self) -> &'static str {
+        match self {
+            Encoding::Webp => "image/webp",
+            Encoding::Avif => "image/avif",
+        }
+    }
+}
+
+/// Speed of the avif encoder, from 1 (slowest, smallest files) to 10.
+const AVIF_SPEED: u8 = 8;
+
+fn default_widths() -> Vec<u32> {
+    vec![480, 960, 1440]
+}
+
+fn default_encodings() -> Vec<Encoding> {
+    vec![Encoding::Webp]
+}
+
+fn default_quality() -> u8 {
+    80
+}
+
+fn default_sizes() -> String {
+    "100vw".to_string()
+}
+
+/// Configured in `[images]` of `site.toml`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ImageConfig {
+    /// Turn co-located raster images into `<picture>` with resized variants.
+    #[serde(default)]
+    pub responsive: bool,
+    /// Widths of the variants, those not smaller than the original are skipped.
+    #[serde(default = "default_widths")]
+    pub widths: Vec<u32>,
+    /// `webp` and/or `avif`, the one preferred by browsers first.
+    #[serde(default = "default_encodings")]
+    pub encodings: Vec<Encoding>,
+    /// Quality of avif encodings, from 1 to 100, webp is encoded losslessly.
+    #[serde(default = "default_quality")]
+    pub quality: u8,
+    /// `sizes` attribute of the generated `srcset`s.
+    #[serde(default = "default_sizes")]
+    pub sizes: String,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            responsive: false,
+            widths: default_widths(),
+            encodings: default_encodings(),
+            quality: default_quality(),
+            sizes: default_sizes(),
+        }
+    }
+}
+
+/// One encoding of an image in several widths.
+pub struct Source {
+    /// `None` for the encoding of the original image
+    pub mime: Option<&'static str>,
+    /// `(width, processed file, file name in the output)`
+    pub variants: Vec<(u32, PathBuf, String)>,
+}
+
+impl ImageConfig {
+    /// Resize and encode the image at `source`, `name` is its content hashed file name.
+    ///
+    /// Returns `None` if it is not a raster image processed by us, processed images are cached
+    /// in `cache_dir`.
+    pub fn process(
+        &self,
+        source: &Path,
+        content: &[u8],
+        name: &str,
+        cache_dir: &Path,
+    ) -> Option<Result<Vec<Source>, String>> {
+        if !self.responsive {
+            return None;
+        }
+        let format = image::guess_format(content).ok()?;
+        if !matches!(
+            format,
+            ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP
+        ) {
+            return None;
+        }
+        Some(self.process_raster(source, content, name, format, cache_dir))
+    }
+
+    fn process_raster(
+        &self,
+        source: &Path,
+        content: &[u8],
+        name: &str,
+        format: ImageFormat,
+        cache_dir: &Path,
+    ) -> Result<Vec<Source>, String> {
+        let (stem, extension) = name.rsplit_once('.').unwrap_or((name, ""));
+        let original_width = imagesize::blob_size(content)
+            .map_err(|e| format!("cannot decode image {}: {}", name, e))?
+            .width as u32;
+        let mut widths: Vec<_> = self
+            .widths
+            .iter()
+            .copied()
+            .filter(|it| *it < original_width)
+            .collect();
+        widths.sort_unstable();
+        widths.push(original_width);
+        let encodings = self
+            .encodings
+            .iter()
+            .map(|it| (Some(*it), it.extension()))
+            .chain(std::iter::once((None, extension)));
+        // Decoded when the first variant missing in the cache is generated
+        let mut decoded: Option<DynamicImage> = None;
+        let mut sources = Vec::new();
+        for (encoding, extension) in encodings {
+            let mut variants = Vec::new();
+            for &width in &widths {
+                if encoding.is_none() && width == original_width {
+                    variants.push((width, source.to_path_buf(), name.to_string()));
+                    continue;
+                }
+                let file_name = format!(
+                    "{}.{}w{}.{}",
+                    stem,
+                    width,
+                    self.settings(encoding),
+                    extension
+                );
+                let cached = cache_dir.join(&file_name);
+                if !cached.exists() {
+                    if decoded.is_none() {
+                        decoded = Some(
+                            image::load_from_memory_with_format(content, format)
+                                .map_err(|e| format!("cannot decode image {}: {}", name, e))?,
+                        );
+                    }
+                    let resized =
+                        decoded
+                            .as_ref()
+                            .unwrap()
+                            .resize(width, u32::MAX, FilterType::Lanczos3);
+                    let encoded = self
+                        .encode(&resized, encoding, format)
+                        .map_err(|e| format!("cannot encode image {}: {}", file_name, e))?;
+                    fs::create_dir_all(cache_dir)
+                        .and_then(|_| fs::write(&cached, encoded))
+                        .map_err(|e| format!("cannot cache image {}: {}", cached.display(), e))?;
+                }
+                variants.push((width, cached, file_name));
+            }
+            sources.push(Source {
+                mime: encoding.map(Encoding::mime),
+                variants,
+            });
+        }
+        Ok(sources)
+    }
+
+    /// Settings an encoding depends on, put into the names of variants so that cached ones
+    /// are not reused once they change, eg. `.q80s8` for avif.
+    fn settings(&self, encoding: Option<Encoding>) -> String {
+        match encoding {
+            Some(Encoding::Avif) => format!(".q{}s{}", self.quality, AVIF_SPEED),
+            Some(Encoding::Webp) | None => String::new(),
+        }
+    }
+
+    fn encode(
+        &self,
+        image: &DynamicImage,
+        encoding: Option<Encoding>,
+        original: ImageFormat,
+    ) -> image::ImageResult<Vec<u8>> {
+        let mut buffer = Vec::new();
+        match encoding {
+            Some(Encoding::Webp) => {
+                image
+                    .to_rgba8()
+                    .write_with_encoder(WebPEncoder::new_lossless(&mut buffer))?;
+            }
+            Some(Encoding::Avif) => {
+                image
+                    .to_rgba8()
+                    .write_with_encoder(AvifEncoder::new_with_speed_quality(
+                        &mut buffer,
+                        AVIF_SPEED,
+                        self.quality,
+                    ))?;
+            }
+            None => image.write_to(&mut Cursor::new(&mut buffer), original)?,
+        }
+        Ok(buffer)
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::info;
+
+use crate::theme::{self, Theme};
+
+/// Scaffold a new wiki under `dir`, laid out like `example/`.
+///
+/// The site goes to `data/`, and the default templates to `templates/` if `eject` is set,
+/// where they are picked up when rendering.
+pub fn init(dir: &Path, languages: &[String], sections: &[String], eject: bool) {
+    if languages.is_empty() {
+        panic!("at least one language is required");
+    }
+    if fs::read_dir(dir).is_ok_and(|mut it| it.next().is_some()) {
+        panic!("{} is not empty", dir.display());
+    }
+    let data = dir.join("data");
+    let title = dir
+        .canonicalize()
+        .ok()
+        .as_deref()
+        .unwrap_or(dir)
+        .file_name()
+        .map(|it| it.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Wiki".to_string());
+    write(
+        data.join("site.toml"),
+        &format!(
+            "title = {}\npublic_url = \"http://localhost:5000\"\ndescription = \"\"\ndefault_language = {}\nlanguages = {}\n",
+            toml::Value::from(title.as_str()),
+            toml::Value::from(languages[0].as_str()),
+            toml::Value::from(languages.to_vec()),
+        ),
+    );
+    for language in languages {
+        let language_dir = data.join(language);
+        write(
+            language_dir.join("translation.toml"),
+            &translation(language),
+        );
+        write(
+            language_dir.join("about.md"),
+            &format!("# {}\n\nWhat this wiki is about.\n", title),
+        );
+        for section in sections {
+            write(
+                language_dir.join(section).join("_section.toml"),
+                &format!("title = {}\n", toml::Value::from(section.as_str())),
+            );
+        }
+        if let Some(section) = sections.first() {
+            let mut front_matter = serde_yaml::Mapping::new();
+            front_matter.insert("category".into(), section.as_str().into());
+            front_matter.insert("tags".into(), serde_yaml::Value::Sequence(Vec::new()));
+            front_matter.insert(
+                "last_update".into(),
+                chrono::Utc::now()
+                    .format("%Y-%m-%dT00:00:00Z")
+                    .to_string()
+                    .into(),
+            );
+            write(
+                language_dir.join(section).join("Welcome.md"),
+                &format!(
+                    "{}\n---\n# Welcome\n\nA sample article, replace it with your own.\n",
+                    serde_yaml::to_string(&front_matter).unwrap().trim_end(),
+                ),
+            );
+        }
+    }
+    if eject {
+        for (name, content) in Theme::embedded().templates {
+            write(dir.join("templates").join(name), &content);
+        }
+    }
+    info!(
+        "Wiki created, render it with `static-wiki -i {} -o <output>`",
+        data.display()
+    );
+}
+
+/// `translation.toml` with all keys used by the default templates.
+fn translation(language: &str) -> String {
+    let default = theme::default_translation(language);
+    if language == "en" || default != theme::default_translation("en") {
+        return default.to_string();
+    }
+    // The English ones, marked to be translated
+    let mut result = String::new();
+    for line in default.lines() {
+        match line.split_once(" = ") {
+            Some(("language_name", _)) => {
+                result += &format!("language_name = {}\n", toml::Value::from(language))
+            }
+            Some(_) => result += &format!("{} # untranslated\n", line),
+            None => result += &format!("{}\n", line),
+        }
+    }
+    result
+}
+
+fn write(path: PathBuf, content: &str) {
+    info!("Write {:?} ...", path);
+    fs::create_dir_all(path.parent().unwrap())
+        .and_then(|_| fs::write(&path, content))
+        .unwrap_or_else(|e| panic!("cannot write {}: {}", path.display(), e));
+}
@@ -1,16 +1,27 @@
-use crate::renderer::Renderer;
-use fs_extra::dir;
+use crate::{assets::AssetPipeline, renderer::Renderer, theme::Theme};
+use chrono::{DateTime, Utc};
 use log::info;
 use model::Site;
-use std::{
-    fs,
-    path::{Path, PathBuf},
-    process::Command,
+use std::path::{Path, PathBuf};
+use structopt::{
+    clap::{Error, ErrorKind},
+    StructOpt,
 };
-use structopt::StructOpt;
+mod admonition;
+mod assets;
+mod config;
+mod diagram;
+mod highlight;
+mod images;
+mod init;
 mod markdown;
+mod math;
 mod model;
+mod output;
 mod renderer;
+mod slug;
+mod theme;
+mod transform;
 
 #[derive(Debug, StructOpt)]
 #[structopt(
@@ -18,101 +29,139 @@ mod renderer;
     about = "Generate static html files and search index for a wiki."
 )]
 struct Opt {
-    /// Input folder
+    /// Input folder, required unless running a subcommand
     #[structopt(parse(from_os_str), short)]
-    input: PathBuf,
+    input: Option<PathBuf>,
 
-    /// Output folder
+    /// Output folder, required unless running a subcommand
     #[structopt(parse(from_os_str), short)]
-    output: PathBuf,
+    output: Option<PathBuf>,
 
-    /// Template folder
+    /// Template folder, its templates replace those of the theme, defaults to `templates`
+    /// next to the input folder if there is one
     #[structopt(parse(from_os_str), short)]
-    template: PathBuf,
+    template: Option<PathBuf>,
 
-    /// Static folder
+    /// Static folder, its files replace those of the theme, defaults to `static` next to the
+    /// input folder if there is one
     #[structopt(parse(from_os_str), short, long = "static")]
-    static_path: PathBuf,
+    static_path: Option<PathBuf>,
+
+    /// Render draft articles
+    #[structopt(long)]
+    drafts: bool,
+
+    /// Time used to decide whether an article is published or expired, defaults to now
+    #[structopt(long)]
+    build_time: Option<DateTime<Utc>>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Create a new wiki with a sample article
+    Init {
+        /// Folder to create the wiki in, must be empty
+        #[structopt(parse(from_os_str))]
+        dir: PathBuf,
+
+        /// Languages of the wiki, the first one is the default
+        #[structopt(long, use_delimiter = true, default_value = "en")]
+        languages: Vec<String>,
+
+        /// Sections of each language
+        #[structopt(long, use_delimiter = true, default_value = "what,how,why")]
+        sections: Vec<String>,
+
+        /// Copy the default templates into the wiki, to be customized
+        #[structopt(long)]
+        eject_templates: bool,
+    },
 }
 
 fn main() {
     env_logger::init();
     let opt: Opt = Opt::from_args();
-    let template_path = opt
+    if let Some(Command::Init {
+        dir,
+        languages,
+        sections,
+        eject_templates,
+    }) = &opt.command
+    {
+        init::init(dir, languages, sections, *eject_templates);
+        return;
+    }
+    let input = opt
+        .input
+        .clone()
+        .unwrap_or_else(|| missing_argument("-i <input>"));
+    let output = opt
+        .output
+        .clone()
+        .unwrap_or_else(|| missing_argument("-o <output>"));
+    info!("Loading data from {:?} ...", input);
+    let build_time = opt.build_time.unwrap_or_else(Utc::now);
+    let mut site = Site::load_from_path(&input, |article| {
+        if article.metadata.draft && !opt.drafts {
+            info!(
+                "Skip draft {}/{} ...",
+                article.section, article.content.filename
+            );
+            false
+        } else if !article.is_published_at(build_time) {
+            info!(
+                "Skip unpublished or expired {}/{} ...",
+                article.section, article.content.filename
+            );
+            false
+        } else {
+            true
+        }
+    });
+    let mut theme = match &site.config.theme {
+        Some(path) => {
+            info!("Loading theme from {:?} ...", input.join(path));
+            Theme::load_from_path(input.join(path))
+        }
+        None => Theme::embedded(),
+    };
+    if let Some(path) = opt
         .template
-        .to_str()
-        .unwrap()
-        .trim_end_matches('/')
-        .to_string()
-        + "/*";
-    info!("Loading templates from {} ...", template_path);
-    let renderer = Renderer::load_from_path(&template_path);
-    info!("Loading data from {:?} ...", opt.input);
-    let site = Site::load_from_path(opt.input);
-    info!("Render to {:?} ...", opt.output);
-    renderer.render_to(site, &opt.output);
-    info!(
-        "Copy static from {:?} to {:?} ...",
-        opt.static_path, opt.output
-    );
-    copy_static(opt.static_path, opt.output);
+        .clone()
+        .or_else(|| sibling(&input, "templates"))
+    {
+        info!("Loading templates from {:?} ...", path);
+        theme.override_templates(path);
+    }
+    if let Some(path) = opt
+        .static_path
+        .clone()
+        .or_else(|| sibling(&input, "static"))
+    {
+        info!("Loading static files from {:?} ...", path);
+        theme.override_static(path);
+    }
+    site.add_default_translations(&theme.translations);
+    let mut renderer = Renderer::new(&theme.templates);
+    info!("Process static files ...");
+    let assets = AssetPipeline::new(&site.config.assets).process(&theme.static_files);
+    info!("Render to {:?} ...", output);
+    renderer.render_to(site, &assets, &output);
 }
 
-fn copy_static(static_path: impl AsRef<Path>, output_base_path: impl AsRef<Path>) {
-    let options = dir::CopyOptions {
-        overwrite: true,
-        copy_inside: true,
-        ..Default::default()
-    };
-    dir::copy(
-        &static_path,
-        &output_base_path.as_ref().join("static"),
-        &options,
-    )
-    .unwrap();
-    fs::copy(
-        &output_base_path
-            .as_ref()
-            .join("static")
-            .join("manifest.json"),
-        &output_base_path.as_ref().join("manifest.json"),
-    )
-    .unwrap();
-    fs::remove_file(
-        &output_base_path
-            .as_ref()
-            .join("static")
-            .join("manifest.json"),
+/// Folder `name` next to `input`, as laid out in `example/`.
+fn sibling(input: &Path, name: &str) -> Option<PathBuf> {
+    let path = input.canonicalize().ok()?.parent()?.join(name);
+    path.is_dir().then_some(path)
+}
+
+fn missing_argument(name: &str) -> ! {
+    Error::with_description(
+        &format!("The following required argument was not provided: {}", name),
+        ErrorKind::MissingRequiredArgument,
     )
-    .unwrap();
-    let pattern = output_base_path
-        .as_ref()
-        .join("static")
-        .to_str()
-        .unwrap()
-        .trim_end_matches('/')
-        .to_string()
-        + "/*.ts";
-    let subprocesses = glob::glob(&pattern)
-        .expect("Failed to read glob pattern")
-        .map(|entry| match entry {
-            Ok(path) => {
-                let command = Command::new("tsc")
-                    .arg("--target")
-                    .arg("es5")
-                    .arg(&path.file_name().unwrap().to_str().unwrap())
-                    .current_dir(output_base_path.as_ref().join("static"))
-                    .spawn()
-                    .expect("failed to execute process");
-                (command, path)
-            }
-            Err(e) => {
-                panic!("{:?}", e)
-            }
-        })
-        .collect::<Vec<_>>();
-    for (mut subprocess, path) in subprocesses {
-        subprocess.wait().unwrap();
-        fs::remove_file(path).unwrap();
-    }
+    .exit()
 }
@@ -1,22 +1,279 @@
-use lazy_static::lazy_static;
-use pulldown_cmark::{html, Event, Options, Parser, Tag};
+use crate::{
+    admonition,
+    config::default_true,
+    math::ExtractedMath,
+    model::{Article, LanguageSite, SiteConfig},
+    slug::SlugConfig,
+    transform::{
+        Asset, CodeBlocks, ColocatedAssets, HeadingIds, MarkdownTransform, TransformContext,
+    },
+};
+use pulldown_cmark::{html, Event, HeadingLevel, Options, Parser, Tag};
 use serde::{Deserialize, Serialize};
-use std::{fs::File, io, io::Read, path::Path};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    io,
+    io::Read,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+use unicode_segmentation::UnicodeSegmentation;
 
-lazy_static! {
-    static ref OPTIONS: Options = {
+/// Byte ranges of the code blocks, fenced or indented, and code spans in `source`,
+/// which other syntax added on top of markdown must leave alone.
+pub fn code_ranges(source: &str) -> Vec<Range<usize>> {
+    Parser::new(source)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(_)) | Event::Code(_) => Some(range),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A heading in the table of contents, headings of lower levels following it are its children.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TocEntry {
+    pub level: u32,
+    pub id: String,
+    /// Html escaped text of the heading
+    pub title: String,
+    pub children: Vec<TocEntry>,
+}
+
+impl TocEntry {
+    fn nest(headings: Vec<(u32, String, String)>) -> Vec<Self> {
+        let mut result: Vec<Self> = Vec::new();
+        for (level, id, title) in headings {
+            let entry = Self {
+                level,
+                id,
+                title,
+                children: Vec::new(),
+            };
+            let mut siblings = &mut result;
+            while siblings.last().is_some_and(|it| it.level < level) {
+                siblings = &mut siblings.last_mut().unwrap().children;
+            }
+            siblings.push(entry);
+        }
+        result
+    }
+}
+
+/// Html rendered from markdown together with the table of contents.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RenderedMarkdown {
+    pub html: String,
+    /// `html` without the first H1
+    pub content_without_title: String,
+    /// Headings except the title
+    pub toc: Vec<TocEntry>,
+    /// Files referenced by the document, to be copied to the output
+    pub assets: Vec<Asset>,
+    /// Problems found while rendering, eg. invalid formulas
+    pub errors: Vec<String>,
+}
+
+/// Configured in `[markdown]` of `site.toml`, articles can override it with `markdown` in front matter.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MarkdownConfig {
+    #[serde(default = "default_true")]
+    pub tables: bool,
+    #[serde(default = "default_true")]
+    pub footnotes: bool,
+    #[serde(default = "default_true")]
+    pub strikethrough: bool,
+    /// `- [ ]` and `- [x]` list items
+    #[serde(default)]
+    pub tasklists: bool,
+    /// Curly quotes, en and em dashes and ellipses
+    #[serde(default)]
+    pub smart_punctuation: bool,
+    /// `# Heading {#id .class}`
+    #[serde(default)]
+    pub heading_attributes: bool,
+    /// Render `$inline$` and `$$display$$` TeX formulas into MathML.
+    #[serde(default)]
+    pub math: bool,
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> Self {
+        Self {
+            tables: true,
+            footnotes: true,
+            strikethrough: true,
+            tasklists: false,
+            smart_punctuation: false,
+            heading_attributes: false,
+            math: false,
+        }
+    }
+}
+
+impl MarkdownConfig {
+    fn options(&self) -> Options {
         let mut options = Options::empty();
-        options.insert(Options::ENABLE_STRIKETHROUGH);
-        options.insert(Options::ENABLE_TABLES);
-        options.insert(Options::ENABLE_FOOTNOTES);
+        options.set(Options::ENABLE_TABLES, self.tables);
+        options.set(Options::ENABLE_FOOTNOTES, self.footnotes);
+        options.set(Options::ENABLE_STRIKETHROUGH, self.strikethrough);
+        options.set(Options::ENABLE_TASKLISTS, self.tasklists);
+        options.set(Options::ENABLE_SMART_PUNCTUATION, self.smart_punctuation);
+        options.set(Options::ENABLE_HEADING_ATTRIBUTES, self.heading_attributes);
         options
-    };
+    }
+
+    /// This config with the options set by an article applied.
+    pub fn with(&self, overrides: &MarkdownOverrides) -> Self {
+        Self {
+            tables: overrides.tables.unwrap_or(self.tables),
+            footnotes: overrides.footnotes.unwrap_or(self.footnotes),
+            strikethrough: overrides.strikethrough.unwrap_or(self.strikethrough),
+            tasklists: overrides.tasklists.unwrap_or(self.tasklists),
+            smart_punctuation: overrides
+                .smart_punctuation
+                .unwrap_or(self.smart_punctuation),
+            heading_attributes: overrides
+                .heading_attributes
+                .unwrap_or(self.heading_attributes),
+            math: overrides.math.unwrap_or(self.math),
+        }
+    }
+}
+
+/// Set by `markdown` in the front matter of an article, options left out follow `site.toml`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct MarkdownOverrides {
+    #[serde(default)]
+    pub tables: Option<bool>,
+    #[serde(default)]
+    pub footnotes: Option<bool>,
+    #[serde(default)]
+    pub strikethrough: Option<bool>,
+    #[serde(default)]
+    pub tasklists: Option<bool>,
+    #[serde(default)]
+    pub smart_punctuation: Option<bool>,
+    #[serde(default)]
+    pub heading_attributes: Option<bool>,
+    #[serde(default)]
+    pub math: Option<bool>,
+}
+
+fn default_summary_length() -> usize {
+    200
+}
+
+fn default_ellipsis() -> String {
+    "…".to_string()
+}
+
+/// Configured in `[summary]` of `site.toml`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SummaryConfig {
+    /// Maximum length of generated summaries in user-perceived characters.
+    #[serde(default = "default_summary_length")]
+    pub length: usize,
+    /// Overrides `length` for some languages, eg. `zh = 80`.
+    #[serde(default)]
+    pub languages: BTreeMap<String, usize>,
+    /// Appended to truncated summaries.
+    #[serde(default = "default_ellipsis")]
+    pub ellipsis: String,
+}
+
+impl Default for SummaryConfig {
+    fn default() -> Self {
+        Self {
+            length: default_summary_length(),
+            languages: BTreeMap::new(),
+            ellipsis: default_ellipsis(),
+        }
+    }
+}
+
+impl SummaryConfig {
+    /// Cut `summary` of an article in `language` to the configured length.
+    pub fn truncate(&self, language: &str, summary: &str) -> String {
+        let length = self.languages.get(language).copied().unwrap_or(self.length);
+        match summary.grapheme_indices(true).nth(length) {
+            Some((end, _)) => format!("{}{}", summary[..end].trim_end(), self.ellipsis),
+            None => summary.to_string(),
+        }
+    }
+}
+
+/// How anchor ids of headings are generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeadingIdStyle {
+    /// Lowercase, spaces replaced by `-` and punctuations removed, like GitHub does
+    #[default]
+    Github,
+    /// The same rules as article slugs, configured in `[slug]` of `site.toml`
+    Slug,
+}
+
+fn default_anchor_symbol() -> String {
+    "#".to_string()
+}
+
+/// Configured in `[headings]` of `site.toml`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HeadingConfig {
+    #[serde(default)]
+    pub ids: HeadingIdStyle,
+    /// Append a link to the heading itself to each heading.
+    #[serde(default)]
+    pub anchors: bool,
+    #[serde(default = "default_anchor_symbol")]
+    pub anchor_symbol: String,
+}
+
+impl Default for HeadingConfig {
+    fn default() -> Self {
+        Self {
+            ids: HeadingIdStyle::default(),
+            anchors: false,
+            anchor_symbol: default_anchor_symbol(),
+        }
+    }
+}
+
+impl HeadingConfig {
+    /// Generate an anchor id from the text of a heading.
+    pub fn id(&self, text: &str, slug: &SlugConfig) -> String {
+        let result = match self.ids {
+            HeadingIdStyle::Github => {
+                let mut result = String::new();
+                for c in text.trim().chars() {
+                    if c.is_alphanumeric() || c == '_' || c == '-' {
+                        result.extend(c.to_lowercase());
+                    } else if c.is_whitespace() {
+                        result.push('-');
+                    }
+                }
+                result
+            }
+            HeadingIdStyle::Slug => slug.slugify(text),
+        };
+        if result.is_empty() {
+            "section".to_string()
+        } else {
+            result
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Markdown {
     pub filename: String,
     content: String,
+    /// Directory of the source file, co-located assets are looked up relative to it.
+    #[serde(skip)]
+    pub dir: PathBuf,
 }
 
 impl Markdown {
@@ -24,6 +281,7 @@ impl Markdown {
         Self {
             filename: filename.to_string(),
             content: content.to_string(),
+            dir: PathBuf::new(),
         }
     }
     pub fn load_from_path(path: impl AsRef<Path>) -> io::Result<Self> {
@@ -31,58 +289,298 @@ impl Markdown {
         let mut file = File::open(path.as_ref())?;
         let mut content = String::new();
         file.read_to_string(&mut content).unwrap();
-        Ok(Self::new(filename, content))
-    }
-    fn parser(&self) -> Parser {
-        Parser::new_ext(&self.content, *OPTIONS)
-    }
-    pub fn html(&self) -> String {
-        let mut html_output = String::new();
-        html::push_html(&mut html_output, self.parser());
-        html_output
-    }
-    pub fn content_without_title(&self) -> String {
-        self.html().splitn(2, "</h1>").nth(1).unwrap().to_string()
-    }
-    pub fn summary(&self) -> String {
-        let mut started = false;
-        let mut result = String::new();
-        for node in self.parser() {
-            match node {
-                Event::Start(Tag::Paragraph) => {
-                    started = true;
+        let mut markdown = Self::new(filename, content);
+        markdown.dir = path.as_ref().parent().unwrap().to_path_buf();
+        Ok(markdown)
+    }
+    fn parser(&self, config: &MarkdownConfig) -> Parser<'_, '_> {
+        Parser::new_ext(&self.content, config.options())
+    }
+    /// Plain text of the first paragraph, falling back to the first list item and then to the
+    /// first heading other than the title, empty if there is none of them.
+    ///
+    /// Callouts are expanded as when rendering, formulas are kept as TeX.
+    pub fn summary(&self, config: &MarkdownConfig) -> String {
+        const PARAGRAPH: usize = 0;
+        const ITEM: usize = 1;
+        const HEADING: usize = 2;
+        let mut found: [Option<String>; 3] = Default::default();
+        // Blocks being collected, the innermost last
+        let mut open: Vec<(usize, String)> = Vec::new();
+        let mut in_image = 0;
+        let source = admonition::expand(&self.content, &admonition::default_label);
+        let (source, math) = if config.math {
+            ExtractedMath::extract(&source)
+        } else {
+            (source, ExtractedMath::default())
+        };
+        for event in Parser::new_ext(&source, config.options()) {
+            match event {
+                Event::Start(Tag::Paragraph) => open.push((PARAGRAPH, String::new())),
+                Event::Start(Tag::Item) => open.push((ITEM, String::new())),
+                Event::Start(Tag::Heading(level, ..)) if level != HeadingLevel::H1 => {
+                    open.push((HEADING, String::new()))
+                }
+                Event::End(Tag::Paragraph | Tag::Item) => {
+                    let (kind, text) = open.pop().unwrap();
+                    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    if !text.is_empty() && found[kind].is_none() {
+                        found[kind] = Some(text);
+                    }
+                    if found[PARAGRAPH].is_some() {
+                        break;
+                    }
                 }
-                Event::Text(t) => {
-                    if started {
-                        result += t.as_ref();
+                Event::End(Tag::Heading(level, ..)) if level != HeadingLevel::H1 => {
+                    let (kind, text) = open.pop().unwrap();
+                    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    if !text.is_empty() && found[kind].is_none() {
+                        found[kind] = Some(text);
                     }
                 }
-                Event::Code(t) => {
-                    if started {
-                        result += t.as_ref();
+                Event::Start(Tag::Image(..)) => in_image += 1,
+                Event::End(Tag::Image(..)) => in_image -= 1,
+                Event::Text(t) | Event::Code(t) if in_image == 0 => {
+                    if let Some((_, text)) = open.last_mut() {
+                        *text += t.as_ref();
                     }
                 }
-                Event::End(Tag::Paragraph) => {
-                    if started {
-                        return result;
+                Event::SoftBreak | Event::HardBreak => {
+                    if let Some((_, text)) = open.last_mut() {
+                        text.push(' ');
                     }
                 }
                 _ => {}
             }
         }
-        unreachable!();
+        let [paragraph, item, heading] = found;
+        math.restore_source(&paragraph.or(item).or(heading).unwrap_or_default())
     }
-    pub fn name(&self) -> String {
-        let mut started = false;
-        for node in self.parser() {
-            if let Event::Start(Tag::Heading(1)) = node {
-                started = true;
-            } else if let Event::Text(t) = node {
-                if started {
-                    return t.into_string();
-                }
+    /// Text of the first H1, or the filename if there is none.
+    pub fn name(&self, config: &MarkdownConfig) -> String {
+        let mut in_title = false;
+        let mut in_image = 0;
+        let mut name = String::new();
+        for event in self.parser(config) {
+            match event {
+                Event::Start(Tag::Heading(HeadingLevel::H1, ..)) => in_title = true,
+                Event::End(Tag::Heading(HeadingLevel::H1, ..)) => break,
+                Event::Start(Tag::Image(..)) => in_image += 1,
+                Event::End(Tag::Image(..)) => in_image -= 1,
+                Event::Text(t) | Event::Code(t) if in_title && in_image == 0 => name += &t,
+                Event::SoftBreak | Event::HardBreak if in_title => name.push(' '),
+                _ => {}
+            }
+        }
+        let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+        if name.is_empty() {
+            self.filename.clone()
+        } else {
+            name
+        }
+    }
+}
+
+/// Turns markdown into html according to the configuration of a site.
+pub struct MarkdownRenderer {
+    config: MarkdownConfig,
+    /// Applied in order, the built-in ones first.
+    transforms: Vec<Box<dyn MarkdownTransform>>,
+}
+
+impl Default for MarkdownRenderer {
+    fn default() -> Self {
+        Self {
+            config: MarkdownConfig::default(),
+            transforms: vec![
+                Box::new(HeadingIds::default()),
+                Box::new(CodeBlocks::default()),
+            ],
+        }
+    }
+}
+
+impl MarkdownRenderer {
+    pub fn new(config: &SiteConfig) -> Self {
+        let mut renderer = Self {
+            config: config.markdown.clone(),
+            transforms: Vec::new(),
+        };
+        renderer.add_transform(HeadingIds {
+            headings: config.headings.clone(),
+            slug: config.slug.clone(),
+        });
+        renderer.add_transform(CodeBlocks {
+            highlight: config.highlight.clone(),
+            diagrams: config.diagrams.clone(),
+            cache: config.cache.clone(),
+        });
+        renderer.add_transform(ColocatedAssets {
+            config: config.clone(),
+        });
+        for transform in &config.transforms {
+            renderer
+                .transforms
+                .push(transform.build(&config.public_url));
+        }
+        renderer
+    }
+
+    /// Split events into those before the first H1, the H1 and those after it.
+    fn split_title(mut events: Vec<Event>) -> [Vec<Event>; 3] {
+        let start = events
+            .iter()
+            .position(|it| matches!(it, Event::Start(Tag::Heading(HeadingLevel::H1, ..))));
+        match start {
+            Some(start) => {
+                let end = start
+                    + events[start..]
+                        .iter()
+                        .position(|it| matches!(it, Event::End(Tag::Heading(..))))
+                        .unwrap();
+                let after = events.split_off(end + 1);
+                let title = events.split_off(start);
+                [events, title, after]
+            }
+            None => [events, Vec::new(), Vec::new()],
+        }
+    }
+
+    /// `[markdown]` of `site.toml`.
+    pub fn config(&self) -> &MarkdownConfig {
+        &self.config
+    }
+
+    pub fn add_transform(&mut self, transform: impl MarkdownTransform + 'static) {
+        self.transforms.push(Box::new(transform));
+    }
+
+    /// Render `markdown`, which is the content of `article` unless it is a top level page.
+    pub fn render(
+        &self,
+        markdown: &Markdown,
+        article: Option<&Article>,
+        language_site: &LanguageSite,
+    ) -> RenderedMarkdown {
+        let config = match article {
+            Some(article) => self.config.with(&article.metadata.markdown),
+            None => self.config.clone(),
+        };
+        let source = admonition::expand(&markdown.content, &|kind| {
+            language_site
+                .translated(&format!("admonition_{}", kind))
+                .map_or_else(|| admonition::default_label(kind), ToString::to_string)
+        });
+        let (source, mut math) = if config.math {
+            ExtractedMath::extract(&source)
+        } else {
+            (source, ExtractedMath::default())
+        };
+        let mut context = TransformContext {
+            markdown,
+            article,
+            language_site,
+            heading_ids: HashSet::new(),
+            headings: Vec::new(),
+            assets: Vec::new(),
+            errors: std::mem::take(&mut math.errors),
+        };
+        // Before the title, the title and after the title, kept apart for `content_without_title`
+        let mut parts = Self::split_title(Parser::new_ext(&source, config.options()).collect());
+        for transform in &self.transforms {
+            for part in &mut parts {
+                *part = transform.transform(std::mem::take(part), &mut context);
             }
         }
-        unreachable!();
+        let [before, title, after] = parts.map(|part| {
+            let mut html_output = String::new();
+            html::push_html(&mut html_output, part.into_iter());
+            math.restore(&html_output)
+        });
+        let headings = context
+            .headings
+            .into_iter()
+            .map(|(level, id, title)| (level, id, math.restore(&title)))
+            .collect();
+        RenderedMarkdown {
+            html: format!("{}{}{}", before, title, after),
+            content_without_title: before + &after,
+            toc: TocEntry::nest(headings),
+            assets: context.assets,
+            errors: context.errors,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn render(source: &str) -> String {
+        let renderer = MarkdownRenderer {
+            config: MarkdownConfig {
+                math: true,
+                ..MarkdownConfig::default()
+            },
+            ..MarkdownRenderer::default()
+        };
+        let language_site = LanguageSite {
+            language: "en".to_string(),
+            sections: HashMap::new(),
+            disambiguation: Vec::new(),
+            top_level_articles: Vec::new(),
+            translation: toml::Value::Table(Default::default()),
+        };
+        renderer
+            .render(&Markdown::new("test", source), None, &language_site)
+            .html
+    }
+
+    #[test]
+    fn heading_ids_leave_formulas_out() {
+        let html = render("## Using $x^2$ now\n\n$a$\n\n## Using $x^2$ now\n");
+        assert!(html.starts_with("<h2 id=\"using-now\">Using <math"));
+        assert!(html.contains("<h2 id=\"using-now-1\">Using <math"));
+        // Formulas before a heading do not change its id
+        assert!(render("$a$ $b$\n\n## Using $x^2$ now\n").contains("<h2 id=\"using-now\">"));
+    }
+
+    #[test]
+    fn summary() {
+        let config = MarkdownConfig {
+            math: true,
+            ..MarkdownConfig::default()
+        };
+        let summary = |source| Markdown::new("test", source).summary(&config);
+        assert_eq!(summary("# Title\n\nFirst `one`.\n\nSecond."), "First one.");
+        assert_eq!(summary("# Title\n\n![image](a.png)\n\n- Item\n"), "Item");
+        assert_eq!(
+            summary(":::warning\nCareful with $x$ here.\n:::\n"),
+            "Careful with x here."
+        );
+        assert_eq!(
+            summary("> [!NOTE]\n> Mind `$` and $$a + b$$.\n"),
+            "Mind $ and a + b."
+        );
+    }
+
+    #[test]
+    fn truncate_summary() {
+        let config = SummaryConfig {
+            length: 5,
+            languages: BTreeMap::from([("zh".to_string(), 2)]),
+            ellipsis: "...".to_string(),
+        };
+        assert_eq!(config.truncate("en", "Hello"), "Hello");
+        assert_eq!(config.truncate("en", "Hello world"), "Hello...");
+        // No whitespace before the ellipsis
+        assert_eq!(config.truncate("en", "Hell world"), "Hell...");
+        assert_eq!(config.truncate("zh", "启用功能"), "启用...");
+        // Combining characters are never split
+        assert_eq!(
+            config.truncate("en", "cafe\u{301} au lait"),
+            "cafe\u{301}..."
+        );
     }
 }
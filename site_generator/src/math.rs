@@ -0,0 +1,234 @@
+use latex2mathml::{latex_to_mathml, DisplayStyle};
+use pulldown_cmark::escape::escape_html;
+
+use crate::markdown::code_ranges;
+
+/// Marks the start of a placeholder of a formula, followed by its index and `PLACEHOLDER_END`.
+const PLACEHOLDER_START: char = '\u{E000}';
+const PLACEHOLDER_END: char = '\u{E001}';
+
+/// Formulas taken out of a markdown document, so that markdown syntax in them is left alone.
+#[derive(Debug, Default)]
+pub struct ExtractedMath {
+    rendered: Vec<String>,
+    /// TeX of the formulas, without delimiters
+    sources: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+impl ExtractedMath {
+    /// Replace `$inline$` and `$$display$$` formulas outside of code in `source` with placeholders.
+    pub fn extract(source: &str) -> (String, Self) {
+        let mut extracted = Self::default();
+        let mut output = String::with_capacity(source.len());
+        let mut code = code_ranges(source).into_iter().peekable();
+        let mut index = 0;
+        while index < source.len() {
+            let code_start = match code.peek() {
+                Some(range) if range.start <= index => {
+                    output += &source[index..range.end];
+                    index = range.end;
+                    code.next();
+                    continue;
+                }
+                Some(range) => range.start,
+                None => source.len(),
+            };
+            // Formulas do not run into code
+            let rest = &source[index..code_start];
+            let c = rest.chars().next().unwrap();
+            if c == '\\' && rest[1..].starts_with('$') {
+                output += "\\$";
+                index += 2;
+            } else if let Some((length, formula, display)) = Self::formula_at(rest) {
+                extracted.push(formula, display, &mut output);
+                index += length;
+            } else {
+                output.push(c);
+                index += c.len_utf8();
+            }
+        }
+        (output, extracted)
+    }
+
+    /// Find a formula starting at the beginning of `text`,
+    /// returns its length in bytes, its content and whether it is a display formula.
+    fn formula_at(text: &str) -> Option<(usize, &str, bool)> {
+        let paragraph = &text[..text.find("\n\n").unwrap_or(text.len())];
+        if let Some(content) = paragraph.strip_prefix("$$") {
+            let end = content.find("$$")?;
+            return Some((end + 4, &content[..end], true));
+        }
+        let content = paragraph.strip_prefix('$')?;
+        if content.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let mut escaped = false;
+        for (at, c) in content.char_indices() {
+            match c {
+                '\\' => escaped = !escaped,
+                '$' if !escaped
+                    && at != 0
+                    && !content[..at].ends_with(char::is_whitespace)
+                    && !content[at + 1..].starts_with(|c: char| c.is_ascii_digit()) =>
+                {
+                    return Some((at + 2, &content[..at], false));
+                }
+                _ => escaped = false,
+            }
+        }
+        None
+    }
+
+    fn push(&mut self, formula: &str, display: bool, output: &mut String) {
+        let style = if display {
+            DisplayStyle::Block
+        } else {
+            DisplayStyle::Inline
+        };
+        let rendered = match latex_to_mathml(formula.trim(), style) {
+            Ok(mathml) => mathml,
+            Err(e) => {
+                self.errors
+                    .push(format!("cannot render formula `{}`: {}", formula.trim(), e));
+                let mut escaped = String::new();
+                escape_html(&mut escaped, formula).unwrap();
+                format!("<code class=\"math-error\">{}</code>", escaped)
+            }
+        };
+        output.push(PLACEHOLDER_START);
+        *output += &self.rendered.len().to_string();
+        output.push(PLACEHOLDER_END);
+        self.rendered.push(rendered);
+        self.sources.push(formula.trim().to_string());
+    }
+
+    /// Put the TeX of the formulas back into plain text taken from the extracted source.
+    pub fn restore_source(&self, text: &str) -> String {
+        replace_placeholders(text, |_, index| self.sources[index].clone())
+    }
+
+    /// Put the rendered formulas back into the html rendered from the extracted source,
+    /// those inside tags, eg. in `alt` of images, as their escaped TeX.
+    pub fn restore(&self, html: &str) -> String {
+        replace_placeholders(html, |before, index| {
+            if before.rfind('<') > before.rfind('>') {
+                let mut escaped = String::new();
+                escape_html(&mut escaped, &self.sources[index]).unwrap();
+                escaped
+            } else {
+                self.rendered[index].clone()
+            }
+        })
+    }
+}
+
+/// Remove formula placeholders from `text`, eg. before making an id of it.
+pub fn strip_placeholders(text: &str) -> String {
+    replace_placeholders(text, |_, _| String::new())
+}
+
+/// Replace each placeholder in `text` by what `f` returns for the index of its formula,
+/// given the text before it.
+fn replace_placeholders(text: &str, mut f: impl FnMut(&str, usize) -> String) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(PLACEHOLDER_START) {
+        result += &rest[..start];
+        let after = &rest[start + PLACEHOLDER_START.len_utf8()..];
+        let end = after.find(PLACEHOLDER_END).unwrap();
+        let replacement = f(&result, after[..end].parse::<usize>().unwrap());
+        result += &replacement;
+        rest = &after[end + PLACEHOLDER_END.len_utf8()..];
+    }
+    result += rest;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Extract and restore `source`, as if the markdown renderer left it untouched.
+    fn roundtrip(source: &str) -> (String, usize) {
+        let (extracted, math) = ExtractedMath::extract(source);
+        (math.restore(&extracted), math.rendered.len())
+    }
+
+    #[test]
+    fn formula_at() {
+        assert_eq!(ExtractedMath::formula_at("$x$ and"), Some((3, "x", false)));
+        assert_eq!(
+            ExtractedMath::formula_at("$$\na + b\n$$"),
+            Some((11, "\na + b\n", true))
+        );
+        assert_eq!(
+            ExtractedMath::formula_at(r"$\$x$"),
+            Some((5, r"\$x", false))
+        );
+        // Unclosed, or closed only after the paragraph ends
+        assert_eq!(ExtractedMath::formula_at("$x"), None);
+        assert_eq!(ExtractedMath::formula_at("$$x"), None);
+        assert_eq!(ExtractedMath::formula_at("$x\n\ny$"), None);
+        // Whitespace inside the dollars
+        assert_eq!(ExtractedMath::formula_at("$ x$"), None);
+        assert_eq!(ExtractedMath::formula_at("$x $"), None);
+    }
+
+    #[test]
+    fn currency_is_not_math() {
+        let source = "It costs $5 or $10.\n";
+        assert_eq!(roundtrip(source), (source.to_string(), 0));
+        let source = "Between $5 and $10 a month\n";
+        assert_eq!(roundtrip(source), (source.to_string(), 0));
+    }
+
+    #[test]
+    fn code_is_not_math() {
+        let source = "Use `$x$` or ``a ` $y$`` here\n\n```sh\necho $HOME$\n```\n";
+        assert_eq!(roundtrip(source), (source.to_string(), 0));
+        let source = "~~~\n$$x$$\n~~~\n";
+        assert_eq!(roundtrip(source), (source.to_string(), 0));
+    }
+
+    #[test]
+    fn indented_code_is_not_math() {
+        let source = "Run\n\n    echo $a$\n\tprintf $b$\n\nThen $c$\n";
+        let (extracted, math) = ExtractedMath::extract(source);
+        assert_eq!(math.rendered.len(), 1);
+        assert!(extracted.starts_with("Run\n\n    echo $a$\n\tprintf $b$\n\nThen \u{E000}"));
+    }
+
+    #[test]
+    fn formulas_in_attributes() {
+        let (extracted, math) = ExtractedMath::extract("![a $x<y$ b](a.png) and $z$");
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(&mut html, pulldown_cmark::Parser::new(&extracted));
+        let restored = math.restore(&html);
+        assert!(restored.starts_with("<p><img src=\"a.png\" alt=\"a x&lt;y b\" /> and <math"));
+        assert_eq!(restored.matches("<math").count(), 1);
+    }
+
+    #[test]
+    fn strip_placeholders() {
+        let (extracted, _) = ExtractedMath::extract("Using $x$ and $$y$$ now");
+        assert_eq!(super::strip_placeholders(&extracted), "Using  and  now");
+    }
+
+    #[test]
+    fn escaped_dollar_is_not_math() {
+        let source = r"Not \$x\$ here";
+        assert_eq!(roundtrip(source), (source.to_string(), 0));
+    }
+
+    #[test]
+    fn extract() {
+        let (extracted, math) = ExtractedMath::extract("Let $x$ be\n\n$$\ny\n$$\n");
+        assert_eq!(math.rendered.len(), 2);
+        assert!(math.errors.is_empty());
+        assert!(!extracted.contains('$'));
+        let restored = math.restore(&extracted);
+        assert!(restored.starts_with("Let <math"));
+        assert!(restored.contains("display=\"block\""));
+    }
+}
@@ -1,11 +1,15 @@
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     fs::{DirEntry, File},
     io::Read,
 };
 
-use crate::markdown::Markdown;
+use crate::{
+    config::default_true,
+    markdown::{Markdown, MarkdownConfig, MarkdownOverrides},
+};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ArticleMeta {
@@ -18,6 +22,39 @@ pub struct ArticleMeta {
     pub author: Option<String>,
     #[serde(default)]
     pub last_update: Option<chrono::DateTime<Utc>>,
+    /// Drafts are not rendered unless `--drafts` is given.
+    #[serde(default)]
+    pub draft: bool,
+    /// Unlisted articles are rendered, but not linked from indexes, tables or the sitemap.
+    #[serde(default)]
+    pub unlisted: bool,
+    /// Overrides the name taken from the first H1 of the content.
+    #[serde(default, skip_serializing)]
+    pub title: Option<String>,
+    /// Overrides the summary taken from the content.
+    #[serde(default, rename = "summary", skip_serializing)]
+    pub summary_override: Option<String>,
+    /// Overrides the slug derived from the filename.
+    #[serde(default, skip_serializing)]
+    pub slug: Option<String>,
+    /// Whether to show the table of contents.
+    #[serde(default = "default_true")]
+    pub toc: bool,
+    /// Old paths of this article, relative to the language site, eg. `what/OldName`.
+    #[serde(default)]
+    pub redirect_from: Vec<String>,
+    /// Articles are not rendered before this time.
+    #[serde(default)]
+    pub publish_date: Option<chrono::DateTime<Utc>>,
+    /// Articles are not rendered from this time on.
+    #[serde(default)]
+    pub expire_date: Option<chrono::DateTime<Utc>>,
+    /// Overrides `[markdown]` of `site.toml` for this article.
+    #[serde(default)]
+    pub markdown: MarkdownOverrides,
+    /// Front matter fields not known by the generator, moved to `Article::extra` once loaded.
+    #[serde(flatten, skip_serializing)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -26,24 +63,55 @@ pub struct Article {
     // filename may different with name, that's why we need disambiguation
     pub name: String,
     pub summary: String,
+    /// Used instead of the filename in the url, unique in the section.
+    #[serde(default)]
+    pub slug: String,
+    #[serde(default)]
+    pub url: String,
     #[serde(flatten)]
     pub metadata: ArticleMeta,
+    /// Front matter fields declared in `site.toml`, nested so they cannot shadow the fields above.
+    #[serde(default)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
     // #[serde(skip_serializing)]
     pub content: Markdown,
 }
 
 impl Article {
-    pub fn new(content: Markdown, meta: ArticleMeta, section: String) -> Self {
+    /// `config` is `[markdown]` of `site.toml`, before the overrides of the article.
+    pub fn new(
+        content: Markdown,
+        mut meta: ArticleMeta,
+        section: String,
+        config: &MarkdownConfig,
+    ) -> Self {
+        let config = config.with(&meta.markdown);
         Self {
+            extra: std::mem::take(&mut meta.extra),
             section,
-            summary: content.summary(),
-            name: content.name(),
+            summary: meta
+                .summary_override
+                .clone()
+                .unwrap_or_else(|| content.summary(&config)),
+            name: meta.title.clone().unwrap_or_else(|| content.name(&config)),
+            slug: content.filename.clone(),
+            url: String::new(),
             content,
             metadata: meta,
         }
     }
 
-    pub fn load(entry: DirEntry) -> Self {
+    /// Page of the rendered article, relative to the language site.
+    pub fn page(&self) -> String {
+        format!("{}/{}", self.section, self.slug)
+    }
+
+    pub fn is_published_at(&self, time: chrono::DateTime<Utc>) -> bool {
+        self.metadata.publish_date.is_none_or(|it| it <= time)
+            && self.metadata.expire_date.is_none_or(|it| time < it)
+    }
+
+    pub fn load(entry: DirEntry, config: &MarkdownConfig) -> Self {
         let section = entry
             .path()
             .parent()
@@ -66,7 +134,8 @@ impl Article {
         iter.next();
         let meta_str = iter.next().unwrap();
         let meta = serde_yaml::from_str(meta_str).unwrap();
-        let content = Markdown::new(filename, iter.next().unwrap());
-        Self::new(content, meta, section)
+        let mut content = Markdown::new(filename, iter.next().unwrap());
+        content.dir = entry.path().parent().unwrap().to_path_buf();
+        Self::new(content, meta, section, config)
     }
 }
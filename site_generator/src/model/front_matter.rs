@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    List,
+}
+
+impl FieldType {
+    fn matches(self, value: &Value) -> bool {
+        match self {
+            FieldType::String => value.is_string(),
+            FieldType::Integer => value.is_i64() || value.is_u64(),
+            FieldType::Float => value.is_number(),
+            FieldType::Boolean => value.is_bool(),
+            FieldType::List => value.is_sequence(),
+        }
+    }
+}
+
+/// Declaration of a custom front matter field in `site.toml`, eg.
+///
+/// ```toml
+/// [front_matter.status]
+/// type = "string"
+/// required = true
+/// allowed = ["draft", "stable"]
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FieldSchema {
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+    #[serde(default)]
+    pub required: bool,
+    /// If not empty, the value (or each item of a list) must be one of these.
+    #[serde(default)]
+    pub allowed: Vec<Value>,
+}
+
+impl FieldSchema {
+    pub fn validate(&self, name: &str, value: Option<&Value>) -> Result<(), String> {
+        let value = match value {
+            None if self.required => return Err(format!("missing required field `{}`", name)),
+            None => return Ok(()),
+            Some(value) => value,
+        };
+        if !self.field_type.matches(value) {
+            return Err(format!(
+                "field `{}` should be of type {:?}, got {:?}",
+                name, self.field_type, value
+            ));
+        }
+        if self.allowed.is_empty() {
+            return Ok(());
+        }
+        let items = match value {
+            Value::Sequence(items) => items.iter().collect(),
+            value => vec![value],
+        };
+        match items.into_iter().find(|it| !self.allowed.contains(it)) {
+            Some(it) => Err(format!(
+                "value {:?} of field `{}` is not one of {:?}",
+                it, name, self.allowed
+            )),
+            None => Ok(()),
+        }
+    }
+}
@@ -1,9 +1,19 @@
 use crate::{
-    markdown::Markdown,
-    model::{Article, ArticleSearchIndex, DisambiguationSearchIndex, SearchIndex, Section},
+    markdown::{Markdown, MarkdownConfig},
+    model::{
+        Article, ArticleSearchIndex, DisambiguationSearchIndex, Redirect, SearchIndex, Section,
+        SiteConfig,
+    },
+    slug::UrlStyle,
 };
+use log::warn;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, convert::TryFrom, fs, fs::File, io::Read};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    fs,
+    path::Path,
+};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Disambiguation {
@@ -29,6 +39,15 @@ impl TryFrom<Vec<Article>> for Disambiguation {
     }
 }
 
+/// Information about a language site used for linking to it from other pages.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LanguageMeta {
+    pub language: String,
+    pub hreflang: String,
+    pub name: String,
+    pub url: String,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LanguageSite {
     pub language: String,
@@ -63,12 +82,8 @@ impl LanguageSite {
     }
 
     fn collect_name_articles_map(
-        sections: impl Iterator<Item = Section>,
+        articles: impl Iterator<Item = Article>,
     ) -> HashMap<String, Vec<Article>> {
-        let articles: Vec<_> = sections
-            .map(|section| section.articles.into_iter())
-            .flatten()
-            .collect();
         let mut result = HashMap::new();
         for article in articles {
             result
@@ -80,26 +95,43 @@ impl LanguageSite {
     }
 
     fn collect_disambiguation(sections: &[Section]) -> Vec<Vec<Article>> {
-        Self::collect_name_articles_map(sections.iter().cloned())
-            .iter()
-            .filter(|(_, articles)| articles.len() > 1)
-            .map(|(_name, article)| article.clone())
-            .collect()
+        Self::collect_name_articles_map(
+            sections
+                .iter()
+                .flat_map(|section| section.articles.iter().cloned()),
+        )
+        .iter()
+        .filter(|(_, articles)| articles.len() > 1)
+        .map(|(_name, article)| article.clone())
+        .collect()
     }
 
-    pub fn collect_search_indexes(&self) -> Vec<SearchIndex> {
+    pub fn collect_search_indexes(&self, config: &SiteConfig) -> Vec<SearchIndex> {
         let (disambiguation_pages, simple_pages): (Vec<_>, Vec<_>) =
-            Self::collect_name_articles_map(self.sections.values().cloned())
-                .values()
-                .cloned()
-                .partition(|articles| articles.len() > 1);
+            Self::collect_name_articles_map(
+                self.sections
+                    .values()
+                    .flat_map(|section| section.articles.iter())
+                    .filter(|article| !article.metadata.unlisted)
+                    .cloned(),
+            )
+            .values()
+            .cloned()
+            .partition(|articles| articles.len() > 1);
         let simple_pages = simple_pages
             .into_iter()
             .filter_map(|it| it.first().cloned());
         disambiguation_pages
             .into_iter()
             .map(|it| it.into())
-            .map(|it: DisambiguationSearchIndex| it.into())
+            .map(|mut it: DisambiguationSearchIndex| {
+                it.url = config.page_url(&format!(
+                    "{}/{}",
+                    self.language,
+                    config.disambiguation_page(&it.name)
+                ));
+                it.into()
+            })
             .chain(
                 simple_pages
                     .map(|it| it.into())
@@ -108,12 +140,12 @@ impl LanguageSite {
             .collect()
     }
 
-    pub(crate) fn load(dir: fs::DirEntry) -> Self {
+    pub(crate) fn load(dir: fs::DirEntry, config: &MarkdownConfig) -> Self {
         let sections_vec: Vec<_> = fs::read_dir(dir.path())
             .unwrap()
             .filter_map(Result::ok)
             .filter(|it| it.metadata().unwrap().is_dir())
-            .map(Section::load)
+            .map(|it| Section::load(it, config))
             .collect();
 
         let raw_files: Vec<_> = fs::read_dir(dir.path())
@@ -133,12 +165,11 @@ impl LanguageSite {
             .collect();
 
         let disambiguation = Self::collect_disambiguation(&sections_vec);
-        let mut translation_file = File::open(dir.path().join("translation.toml")).unwrap();
-        let mut translation_content = String::new();
-        translation_file
-            .read_to_string(&mut translation_content)
-            .unwrap();
-        let translation: toml::Value = toml::from_str(&translation_content).unwrap();
+        // Translations may all come from the theme
+        let translation = match fs::read_to_string(dir.path().join("translation.toml")) {
+            Ok(content) => toml::from_str(&content).unwrap(),
+            Err(_) => toml::Value::Table(Default::default()),
+        };
 
         Self::new(
             dir.file_name().into_string().unwrap(),
@@ -149,7 +180,244 @@ impl LanguageSite {
         )
     }
 
+    /// A string from `translation.toml`.
+    pub fn translated(&self, key: &str) -> Option<&str> {
+        self.translation.get(key).and_then(toml::Value::as_str)
+    }
+
+    pub fn meta(&self, config: &SiteConfig) -> LanguageMeta {
+        let translated = |key: &str| self.translated(key).map(ToString::to_string);
+        LanguageMeta {
+            language: self.language.clone(),
+            hreflang: translated("hreflang").unwrap_or_else(|| self.language.clone()),
+            name: translated("language_name").unwrap_or_else(|| self.language.clone()),
+            url: config.page_url(&format!("{}/index", self.language)),
+        }
+    }
+
+    /// Keep only articles for which `f` returns true.
+    pub fn retain_articles(&mut self, mut f: impl FnMut(&Article) -> bool) {
+        for section in self.sections.values_mut() {
+            section.articles.retain(|article| f(article));
+        }
+        let sections: Vec<_> = self.sections.values().cloned().collect();
+        self.disambiguation = Self::collect_disambiguation(&sections)
+            .into_iter()
+            .map(|x| Disambiguation::try_from(x).unwrap())
+            .collect();
+    }
+
+    /// Aliases and `redirect_from` entries leaving the language site, reported like slugs.
+    pub fn redirect_errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        for article in self.sections.values().flat_map(|section| &section.articles) {
+            let entries = article
+                .metadata
+                .aliases
+                .iter()
+                .map(|it| ("alias", it))
+                .chain(
+                    article
+                        .metadata
+                        .redirect_from
+                        .iter()
+                        .map(|it| ("redirect_from", it)),
+                );
+            for (field, path) in entries {
+                if !is_inside(path) {
+                    errors.push(format!(
+                        "{}/{}.md: {} `{}` must be a relative path without `.` or `..`",
+                        article.section, article.content.filename, field, path
+                    ));
+                }
+            }
+        }
+        errors
+    }
+
+    /// Redirects from aliases, `redirect_from`, filenames and other url styles of the articles
+    /// to the articles themselves, entries rejected by `redirect_errors` are left out.
+    pub fn redirects(&self, url_style: UrlStyle) -> Vec<Redirect> {
+        let mut articles: Vec<_> = self
+            .sections
+            .values()
+            .flat_map(|section| section.articles.iter())
+            .collect();
+        articles.sort_by_key(|article| article.page());
+        // Index pages of the language site and its sections are never redirected
+        let indexes = self
+            .sections
+            .keys()
+            .map(|section| format!("{}/index", section))
+            .chain(std::iter::once("index".to_string()))
+            .flat_map(|page| [url_style.file(&page), UrlStyle::Html.file(&page)]);
+        let mut taken: HashSet<String> = articles
+            .iter()
+            .map(|article| url_style.file(&article.page()))
+            .chain(indexes)
+            .collect();
+        let mut result = Vec::new();
+        for article in articles {
+            let file = url_style.file(&article.page());
+            let aliases = article
+                .metadata
+                .aliases
+                .iter()
+                .filter(|alias| is_inside(alias))
+                .map(|alias| url_style.file(&format!("{}/{}", article.section, alias)));
+            let redirect_from = article
+                .metadata
+                .redirect_from
+                .iter()
+                .filter(|from| is_inside(from))
+                .map(|from| {
+                    if Path::new(from).extension().is_some() {
+                        from.to_string()
+                    } else {
+                        url_style.file(from)
+                    }
+                });
+            let old_urls = vec![
+                UrlStyle::Html.file(&format!("{}/{}", article.section, article.content.filename)),
+                UrlStyle::Html.file(&article.page()),
+            ];
+            for from in aliases.chain(redirect_from).chain(old_urls) {
+                if from == file || result.iter().any(|it: &Redirect| it.from == from) {
+                    continue;
+                }
+                if !taken.insert(from.clone()) {
+                    warn!(
+                        "{}/{} is already taken, cannot redirect it to {}",
+                        self.language,
+                        from,
+                        article.page()
+                    );
+                    continue;
+                }
+                result.push(Redirect {
+                    from,
+                    to: article.page(),
+                });
+            }
+        }
+        for redirect in &mut result {
+            redirect.from = format!("{}/{}", self.language, redirect.from);
+            redirect.to = format!("{}/{}", self.language, redirect.to);
+        }
+        result
+    }
+
+    /// Number of listed articles, unlisted ones are not revealed by the count.
     pub fn article_count(&self) -> usize {
-        self.sections.iter().map(|(_, it)| it.articles.len()).sum()
+        self.sections
+            .values()
+            .flat_map(|it| &it.articles)
+            .filter(|it| !it.metadata.unlisted)
+            .count()
+    }
+}
+
+/// Whether a relative `path` stays inside the folder it is relative to.
+fn is_inside(path: &str) -> bool {
+    !path.starts_with('/') && path.split('/').all(|it| !matches!(it, "" | "." | ".."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn article(section: &str, filename: &str, slug: &str, front_matter: &str) -> Article {
+        let meta = serde_yaml::from_str(&format!("category: {}\n{}", section, front_matter));
+        let mut article = Article::new(
+            Markdown::new(filename, "# Name\n"),
+            meta.unwrap(),
+            section.to_string(),
+            &MarkdownConfig::default(),
+        );
+        article.slug = slug.to_string();
+        article
+    }
+
+    fn language_site(articles: Vec<Article>) -> LanguageSite {
+        let section = Section {
+            name: "what".to_string(),
+            articles,
+            metadata: Default::default(),
+        };
+        LanguageSite::new(
+            "en".to_string(),
+            vec![section],
+            Vec::new(),
+            Vec::new(),
+            toml::Value::Table(Default::default()),
+        )
+    }
+
+    fn pairs(site: &LanguageSite) -> Vec<(String, String)> {
+        let mut result: Vec<_> = site
+            .redirects(UrlStyle::Html)
+            .into_iter()
+            .map(|it| (it.from, it.to))
+            .collect();
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn redirects() {
+        let site = language_site(vec![article(
+            "what",
+            "Old Name",
+            "New",
+            "aliases: [alias]\nredirect_from: [how/Moved, old.php]",
+        )]);
+        assert!(site.redirect_errors().is_empty());
+        assert_eq!(
+            pairs(&site),
+            [
+                ("en/how/Moved.html", "en/what/New"),
+                ("en/old.php", "en/what/New"),
+                ("en/what/Old Name.html", "en/what/New"),
+                ("en/what/alias.html", "en/what/New"),
+            ]
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+        );
+    }
+
+    #[test]
+    fn redirects_leaving_the_language_site() {
+        let site = language_site(vec![article(
+            "what",
+            "A",
+            "A",
+            "aliases: [../../../escaped, ./here]\n\
+             redirect_from: [../../../escaped.txt, /etc/passwd, what/../../x, what//x, '']",
+        )]);
+        assert_eq!(site.redirect_errors().len(), 7);
+        assert!(pairs(&site).is_empty());
+    }
+
+    #[test]
+    fn redirects_to_taken_pages() {
+        let site = language_site(vec![
+            article(
+                "what",
+                "A",
+                "A",
+                "aliases: [index, B, Same]\nredirect_from: [index, what/index, what/Same]",
+            ),
+            article(
+                "what",
+                "B",
+                "B",
+                "aliases: [Same]\nredirect_from: [what/Same]",
+            ),
+        ]);
+        // Index pages and articles are never replaced, the first article gets a shared entry
+        assert_eq!(
+            pairs(&site),
+            [("en/what/Same.html", "en/what/A")]
+                .map(|(from, to)| (from.to_string(), to.to_string()))
+        );
     }
 }
@@ -1,11 +1,15 @@
 pub use article::Article;
-pub use language_site::LanguageSite;
+pub use front_matter::FieldSchema;
+pub use language_site::{LanguageMeta, LanguageSite};
+pub use redirect::{Redirect, RedirectFormat};
 pub use section::Section;
 pub use site::{Site, SiteConfig};
 pub use site_index::{ArticleSearchIndex, DisambiguationSearchIndex, SearchIndex};
 
 mod article;
+mod front_matter;
 mod language_site;
+mod redirect;
 mod section;
 mod site;
 mod site_index;
@@ -0,0 +1,62 @@
+use crate::model::SiteConfig;
+use serde::{Deserialize, Serialize};
+
+/// A page which should send visitors to another page.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Redirect {
+    /// File of the old page, relative to the output root, eg. `zh/what/OldName.html`
+    pub from: String,
+    /// Page redirected to, relative to the output root, eg. `zh/what/NewName`
+    pub to: String,
+}
+
+/// Server specific redirect files which can be generated besides the redirect pages.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedirectFormat {
+    /// `_redirects` file understood by Netlify and Cloudflare Pages
+    Netlify,
+    /// `redirects.map` file to be included in an nginx `map` block
+    Nginx,
+}
+
+impl RedirectFormat {
+    pub fn filename(self) -> &'static str {
+        match self {
+            RedirectFormat::Netlify => "_redirects",
+            RedirectFormat::Nginx => "redirects.map",
+        }
+    }
+
+    pub fn render(self, redirects: &[Redirect], config: &SiteConfig) -> String {
+        redirects
+            .iter()
+            .map(|redirect| {
+                let to = config.page_url(&redirect.to);
+                let from = redirect
+                    .from
+                    .strip_suffix("index.html")
+                    .unwrap_or(&redirect.from);
+                match self {
+                    RedirectFormat::Netlify => {
+                        format!("{} {} 301\n", url_path(&config.url(from)), to)
+                    }
+                    RedirectFormat::Nginx => format!(
+                        "{:?} {:?};\n",
+                        format!("{}/{}", url_path(&config.public_url), from),
+                        to
+                    ),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The path part of an url, eg. `/wiki/zh` for `https://example.com/wiki/zh`.
+fn url_path(url: &str) -> &str {
+    let url = url.trim_end_matches('/');
+    let host_and_path = url.split_once("://").map_or(url, |(_, rest)| rest);
+    host_and_path
+        .find('/')
+        .map_or("", |index| &host_and_path[index..])
+}
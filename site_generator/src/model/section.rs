@@ -1,15 +1,19 @@
-use crate::model::Article;
+use crate::{markdown::MarkdownConfig, model::Article, slug::SlugConfig};
+use log::warn;
 use serde::{Deserialize, Serialize};
-use std::{fs, fs::DirEntry};
+use std::{collections::HashSet, fs, fs::DirEntry};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Section {
     pub name: String,
     pub articles: Vec<Article>,
+    /// Content of `_section.toml` in the section folder, if any.
+    #[serde(default)]
+    pub metadata: toml::value::Table,
 }
 
 impl Section {
-    pub fn load(dir: DirEntry) -> Self {
+    pub fn load(dir: DirEntry, config: &MarkdownConfig) -> Self {
         let name = dir
             .path()
             .file_name()
@@ -20,11 +24,158 @@ impl Section {
         let articles = fs::read_dir(dir.path())
             .unwrap()
             .filter_map(|it| match it {
-                Ok(it) if it.path().extension().unwrap() == "md" => Some(it),
+                Ok(it) if it.path().extension().is_some_and(|it| it == "md") => Some(it),
                 _ => None,
             })
-            .map(Article::load)
+            .map(|it| Article::load(it, config))
             .collect();
-        Self { name, articles }
+        let metadata_path = dir.path().join("_section.toml");
+        let metadata = if metadata_path.exists() {
+            let content = fs::read_to_string(&metadata_path).unwrap();
+            toml::from_str(&content)
+                .unwrap_or_else(|e| panic!("{}: {}", metadata_path.display(), e))
+        } else {
+            toml::value::Table::new()
+        };
+        Self {
+            name,
+            articles,
+            metadata,
+        }
+    }
+
+    /// Give each article a slug unique in this section, returns problems of slugs set in front matter.
+    ///
+    /// Slugs set in front matter take precedence over those derived from filenames,
+    /// colliding slugs get a numeric suffix. `index` is taken by the section page.
+    pub fn assign_slugs(&mut self, config: &SlugConfig) -> Vec<String> {
+        self.articles.sort_by(|a, b| {
+            (a.metadata.slug.is_none(), &a.content.filename)
+                .cmp(&(b.metadata.slug.is_none(), &b.content.filename))
+        });
+        let mut errors = Vec::new();
+        let mut taken = HashSet::new();
+        taken.insert("index".to_string());
+        for article in &mut self.articles {
+            let wanted = match &article.metadata.slug {
+                Some(slug) if slug.contains('/') || slug.trim().starts_with('.') => {
+                    errors.push(format!(
+                        "{}/{}.md: slug `{}` must not contain `/` or start with `.`",
+                        self.name, article.content.filename, slug
+                    ));
+                    config.slugify(&article.content.filename)
+                }
+                Some(slug) if slug.trim().is_empty() => {
+                    errors.push(format!(
+                        "{}/{}.md: slug must not be empty",
+                        self.name, article.content.filename
+                    ));
+                    config.slugify(&article.content.filename)
+                }
+                Some(slug) => slug.trim().to_string(),
+                None => config.slugify(&article.content.filename),
+            };
+            let wanted = if wanted.is_empty() {
+                article.content.filename.trim_start_matches('.').to_string()
+            } else {
+                wanted
+            };
+            if wanted.is_empty() {
+                errors.push(format!(
+                    "{}/{}.md: no slug can be derived from the filename, set `slug` in front matter",
+                    self.name, article.content.filename
+                ));
+                continue;
+            }
+            let mut slug = wanted.clone();
+            let mut suffix = 2;
+            while !taken.insert(slug.clone()) {
+                slug = format!("{}{}{}", wanted, config.separator, suffix);
+                suffix += 1;
+            }
+            if slug != wanted {
+                warn!(
+                    "slug {} of {}/{} collides with another article, use {} instead",
+                    wanted, self.name, article.content.filename, slug
+                );
+            }
+            article.slug = slug;
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markdown::{Markdown, MarkdownConfig};
+
+    fn section(articles: &[(&str, Option<&str>)]) -> Section {
+        let articles = articles
+            .iter()
+            .map(|(filename, slug)| {
+                let mut meta: crate::model::article::ArticleMeta =
+                    serde_yaml::from_str("category: what").unwrap();
+                meta.slug = slug.map(ToString::to_string);
+                Article::new(
+                    Markdown::new(filename, "# Name\n"),
+                    meta,
+                    "what".to_string(),
+                    &MarkdownConfig::default(),
+                )
+            })
+            .collect();
+        Section {
+            name: "what".to_string(),
+            articles,
+            metadata: Default::default(),
+        }
+    }
+
+    fn slugs(section: &Section) -> Vec<(&str, &str)> {
+        section
+            .articles
+            .iter()
+            .map(|it| (it.content.filename.as_str(), it.slug.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn assign_slugs() {
+        let mut section = section(&[
+            ("Hello World", None),
+            ("index", None),
+            ("Other", Some("Hello-World")),
+            ("启用", None),
+            ("!!", None),
+        ]);
+        assert!(section.assign_slugs(&SlugConfig::default()).is_empty());
+        assert_eq!(
+            slugs(&section),
+            [
+                ("Other", "Hello-World"),
+                ("!!", "!!"),
+                ("Hello World", "Hello-World-2"),
+                ("index", "index-2"),
+                ("启用", "启用"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_slugs_leaving_the_section() {
+        for slug in ["a/b", ".", "..", ".hidden", "", " "] {
+            let mut section = section(&[("A", Some(slug))]);
+            assert_eq!(
+                section.assign_slugs(&SlugConfig::default()).len(),
+                1,
+                "{}",
+                slug
+            );
+            assert_eq!(slugs(&section), [("A", "A")]);
+        }
+        let mut section = section(&[("..", None), (".hidden", None)]);
+        assert_eq!(section.assign_slugs(&SlugConfig::default()).len(), 1);
+        assert_eq!(slugs(&section)[1], (".hidden", "hidden"));
     }
 }
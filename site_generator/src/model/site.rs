@@ -1,12 +1,74 @@
-use crate::model::LanguageSite;
+use crate::{
+    assets::AssetConfig,
+    config::CacheConfig,
+    diagram::DiagramConfig,
+    highlight::HighlightConfig,
+    images::ImageConfig,
+    markdown::{HeadingConfig, MarkdownConfig, SummaryConfig},
+    model::{Article, FieldSchema, LanguageMeta, LanguageSite, RedirectFormat},
+    output::OutputConfig,
+    slug::{SlugConfig, UrlStyle},
+    transform::TransformConfig,
+};
+use log::{error, warn};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, fs::File, io::Read, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SiteConfig {
     pub title: String,
     pub public_url: String,
     pub description: String,
+    /// Input folder `site.toml` is loaded from, documents may only refer to files inside it.
+    #[serde(skip)]
+    pub input: PathBuf,
+    /// Directory of the theme, relative to the input folder, which is not a language if inside it.
+    #[serde(default)]
+    pub theme: Option<PathBuf>,
+    /// Language the root page falls back to when none of the visitor's
+    /// preferred languages is available.
+    #[serde(default)]
+    pub default_language: Option<String>,
+    /// Order in which languages are offered to the visitor.
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// Custom front matter fields articles may (or must) have.
+    #[serde(default)]
+    pub front_matter: BTreeMap<String, FieldSchema>,
+    /// Server specific redirect files to generate, `netlify` and/or `nginx`.
+    #[serde(default)]
+    pub redirect_files: Vec<RedirectFormat>,
+    #[serde(default)]
+    pub slug: SlugConfig,
+    /// `html` or `pretty`, see `UrlStyle`.
+    #[serde(default)]
+    pub url_style: UrlStyle,
+    #[serde(default)]
+    pub headings: HeadingConfig,
+    #[serde(default)]
+    pub highlight: HighlightConfig,
+    #[serde(default)]
+    pub markdown: MarkdownConfig,
+    #[serde(default)]
+    pub diagrams: DiagramConfig,
+    #[serde(default)]
+    pub transforms: Vec<TransformConfig>,
+    #[serde(default)]
+    pub summary: SummaryConfig,
+    #[serde(default)]
+    pub images: ImageConfig,
+    #[serde(default)]
+    pub assets: AssetConfig,
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
 }
 
 impl SiteConfig {
@@ -20,6 +82,25 @@ impl SiteConfig {
         let file = File::open(path.as_ref()).unwrap();
         Self::load(file)
     }
+
+    /// Url of a path relative to the output root.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.public_url, self.slug.encode_path(path))
+    }
+
+    /// Url of a page, all links to generated pages are built by this.
+    pub fn page_url(&self, page: &str) -> String {
+        self.url(&self.url_style.link(page))
+    }
+
+    /// Page of the disambiguation page for articles sharing `name`, relative to the language site.
+    pub fn disambiguation_page(&self, name: &str) -> String {
+        let slug = self.slug.slugify(name);
+        format!(
+            "disambiguation/{}",
+            if slug.is_empty() { name } else { &slug }
+        )
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -41,14 +122,182 @@ impl Site {
         }
     }
 
-    pub fn load_from_path(path: impl AsRef<Path>) -> Self {
-        let config = SiteConfig::load_from_path(path.as_ref().join("site.toml"));
-        let language_site_vec = fs::read_dir(path)
+    /// Load the site, keeping only articles for which `keep` returns true, before any slug
+    /// or redirect is given to them.
+    pub fn load_from_path(path: impl AsRef<Path>, keep: impl FnMut(&Article) -> bool) -> Self {
+        let mut config = SiteConfig::load_from_path(path.as_ref().join("site.toml"));
+        config.input = path.as_ref().to_path_buf();
+        // The theme may be kept in the input folder too
+        let theme = config
+            .theme
+            .as_ref()
+            .and_then(|it| path.as_ref().join(it).canonicalize().ok());
+        let language_site_vec: Vec<_> = fs::read_dir(path.as_ref())
             .unwrap()
             .filter_map(|it| it.ok())
             .filter(|it| it.metadata().unwrap().is_dir())
-            .map(LanguageSite::load)
+            .filter(|it| theme.is_none() || it.path().canonicalize().ok() != theme)
+            .map(|it| LanguageSite::load(it, &config.markdown))
             .collect();
-        Self::new(config, language_site_vec)
+        if language_site_vec.is_empty() {
+            panic!(
+                "no language folder found in {}, articles go to <language>/<section>/*.md",
+                path.as_ref().display()
+            );
+        }
+        let mut site = Self::new(config, language_site_vec);
+        site.retain_articles(keep);
+        site.validate_front_matter();
+        site.assign_urls();
+        site.validate_redirects();
+        site.truncate_summaries();
+        site
+    }
+
+    fn assign_urls(&mut self) {
+        let mut error_count = 0;
+        for language_site in self.language_sites.values_mut() {
+            for section in language_site.sections.values_mut() {
+                for e in section.assign_slugs(&self.config.slug) {
+                    error!("{}/{}", language_site.language, e);
+                    error_count += 1;
+                }
+                for article in &mut section.articles {
+                    article.url = self.config.page_url(&format!(
+                        "{}/{}",
+                        language_site.language,
+                        article.page()
+                    ));
+                }
+            }
+        }
+        if error_count != 0 {
+            panic!("{} invalid slug(s) found", error_count);
+        }
+    }
+
+    fn validate_redirects(&self) {
+        let mut error_count = 0;
+        for language_site in self.language_sites.values() {
+            for e in language_site.redirect_errors() {
+                error!("{}/{}", language_site.language, e);
+                error_count += 1;
+            }
+        }
+        if error_count != 0 {
+            panic!("{} invalid redirect(s) found", error_count);
+        }
+    }
+
+    fn truncate_summaries(&mut self) {
+        for language_site in self.language_sites.values_mut() {
+            for article in language_site
+                .sections
+                .values_mut()
+                .flat_map(|it| &mut it.articles)
+                .filter(|it| it.metadata.summary_override.is_none())
+            {
+                article.summary = self
+                    .config
+                    .summary
+                    .truncate(&language_site.language, &article.summary);
+            }
+        }
+    }
+
+    /// Fill in translations missing in `translation.toml` from `defaults`, by language.
+    ///
+    /// Languages without defaults get the English ones, named by their language code.
+    pub fn add_default_translations(&mut self, defaults: &HashMap<String, toml::value::Table>) {
+        for language_site in self.language_sites.values_mut() {
+            let language = &language_site.language;
+            let defaults = match defaults.get(language) {
+                Some(defaults) => defaults.clone(),
+                None => {
+                    let mut defaults = defaults.get("en").cloned().unwrap_or_default();
+                    defaults.insert("language_name".to_string(), language.as_str().into());
+                    defaults
+                }
+            };
+            if let Some(translation) = language_site.translation.as_table_mut() {
+                for (key, value) in defaults {
+                    translation.entry(key).or_insert(value);
+                }
+            }
+        }
+    }
+
+    /// Keep only articles for which `f` returns true.
+    fn retain_articles(&mut self, mut f: impl FnMut(&Article) -> bool) {
+        for language_site in self.language_sites.values_mut() {
+            language_site.retain_articles(&mut f);
+        }
+    }
+
+    fn validate_front_matter(&self) {
+        let mut error_count = 0;
+        for language_site in self.language_sites.values() {
+            for article in language_site.sections.values().flat_map(|it| &it.articles) {
+                let extra = &article.extra;
+                for (name, schema) in &self.config.front_matter {
+                    if let Err(e) = schema.validate(name, extra.get(name)) {
+                        error!(
+                            "{}/{}/{}.md: {}",
+                            language_site.language, article.section, article.content.filename, e
+                        );
+                        error_count += 1;
+                    }
+                }
+                for name in extra.keys() {
+                    if !self.config.front_matter.is_empty()
+                        && !self.config.front_matter.contains_key(name)
+                    {
+                        warn!(
+                            "{}/{}/{}.md: field `{}` is not declared in site.toml",
+                            language_site.language, article.section, article.content.filename, name
+                        );
+                    }
+                }
+            }
+        }
+        if error_count != 0 {
+            panic!("{} invalid front matter field(s) found", error_count);
+        }
+    }
+
+    /// All languages of this site, those listed in `languages` of `site.toml` go first,
+    /// the rest are sorted by name.
+    pub fn languages(&self) -> Vec<&str> {
+        let mut result: Vec<&str> = self
+            .config
+            .languages
+            .iter()
+            .map(String::as_str)
+            .filter(|it| self.language_sites.contains_key(*it))
+            .collect();
+        let mut rest: Vec<&str> = self
+            .language_sites
+            .keys()
+            .map(String::as_str)
+            .filter(|it| !result.contains(it))
+            .collect();
+        rest.sort_unstable();
+        result.append(&mut rest);
+        result
+    }
+
+    pub fn default_language(&self) -> &str {
+        self.config
+            .default_language
+            .as_deref()
+            .filter(|it| self.language_sites.contains_key(*it))
+            .unwrap_or_else(|| self.languages()[0])
+    }
+
+    pub fn language_metas(&self) -> Vec<LanguageMeta> {
+        self.languages()
+            .into_iter()
+            .map(|it| self.language_sites[it].meta(&self.config))
+            .collect()
     }
 }
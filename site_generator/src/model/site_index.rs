@@ -1,6 +1,8 @@
 use crate::model::Article;
 use derive_more::From;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ArticleSearchIndex {
     #[serde(default)]
@@ -17,6 +19,10 @@ pub struct ArticleSearchIndex {
     name: String,
     #[serde(default)]
     filename: String,
+    #[serde(default)]
+    url: String,
+    #[serde(default)]
+    extra: BTreeMap<String, serde_yaml::Value>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -24,6 +30,8 @@ pub struct DisambiguationSearchIndex {
     #[serde(default)]
     pub name: String,
     #[serde(default)]
+    pub url: String,
+    #[serde(default)]
     pub articles: Vec<ArticleSearchIndex>,
 }
 
@@ -39,6 +47,7 @@ impl From<Vec<Article>> for DisambiguationSearchIndex {
         assert_ne!(articles.len(), 0);
         Self {
             name: articles[0].name.clone(),
+            url: String::new(),
             articles: articles.into_iter().map(Article::into).collect(),
         }
     }
@@ -54,6 +63,8 @@ impl From<Article> for ArticleSearchIndex {
             summary: article.summary,
             name: article.name,
             filename: article.content.filename,
+            url: article.url,
+            extra: article.extra,
         }
     }
 }
@@ -0,0 +1,153 @@
+use std::{collections::BTreeMap, fs, io::Write, path::Path};
+
+use flate2::{write::GzEncoder, Compression as GzipLevel};
+use log::info;
+use minify_html::Cfg;
+use serde::{Deserialize, Serialize};
+
+use crate::assets;
+
+/// Files worth compressing, others like images are compressed already.
+const TEXT_EXTENSIONS: [&str; 8] = ["html", "css", "js", "mjs", "json", "xml", "svg", "txt"];
+
+/// Precompressed siblings written next to text files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Gzip,
+    Brotli,
+}
+
+impl Compression {
+    fn extension(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Brotli => "br",
+        }
+    }
+
+    fn compress(self, content: &[u8]) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        match self {
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(&mut buffer, GzipLevel::best());
+                encoder.write_all(content).unwrap();
+                encoder.finish().unwrap();
+            }
+            Compression::Brotli => {
+                let mut encoder = brotli::CompressorWriter::new(&mut buffer, 4096, 11, 22);
+                encoder.write_all(content).unwrap();
+                encoder.into_inner();
+            }
+        }
+        buffer
+    }
+}
+
+/// Configured in `[output]` of `site.toml`, applied once everything is rendered.
+///
+/// Static files are minified by `minify` of `[assets]`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct OutputConfig {
+    /// Minify html pages and CSS inside them, inline scripts are kept as is like static ones.
+    #[serde(default)]
+    pub minify: bool,
+    /// `gzip` and/or `brotli`, for servers able to serve precompressed files.
+    #[serde(default)]
+    pub precompress: Vec<Compression>,
+}
+
+/// Bytes written for files of one extension.
+#[derive(Default)]
+struct Sizes {
+    files: usize,
+    rendered: usize,
+    written: usize,
+    compressed: BTreeMap<Compression, usize>,
+}
+
+impl OutputConfig {
+    /// Minify and compress files under `output`, logging their sizes.
+    pub fn post_process(&self, output: impl AsRef<Path>) {
+        let minify = Cfg {
+            minify_css: true,
+            minify_js: false,
+            ..Cfg::spec_compliant()
+        };
+        let mut sizes: BTreeMap<String, Sizes> = BTreeMap::new();
+        for path in assets::files(output.as_ref()) {
+            let extension = path
+                .extension()
+                .map(|it| it.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            let mut content = fs::read(&path).unwrap();
+            let sizes = sizes.entry(extension.clone()).or_default();
+            sizes.files += 1;
+            sizes.rendered += content.len();
+            if self.minify && extension == "html" {
+                let minified = minify_html::minify(&content, &minify);
+                if minified.len() < content.len() {
+                    content = minified;
+                    fs::write(&path, &content).unwrap();
+                }
+            }
+            sizes.written += content.len();
+            if !TEXT_EXTENSIONS.contains(&extension.as_str()) {
+                continue;
+            }
+            for compression in &self.precompress {
+                let compressed = compression.compress(&content);
+                // Served as is otherwise, eg. files too small to benefit
+                let size = if compressed.len() < content.len() {
+                    let mut name = path.file_name().unwrap().to_os_string();
+                    name.push(".");
+                    name.push(compression.extension());
+                    fs::write(path.with_file_name(name), &compressed).unwrap();
+                    compressed.len()
+                } else {
+                    content.len()
+                };
+                *sizes.compressed.entry(*compression).or_default() += size;
+            }
+        }
+        let mut total = Sizes::default();
+        for (extension, sizes) in &sizes {
+            info!("{}", sizes.summary(extension));
+            total.files += sizes.files;
+            total.rendered += sizes.rendered;
+            total.written += sizes.written;
+            for (compression, size) in &sizes.compressed {
+                *total.compressed.entry(*compression).or_default() += size;
+            }
+        }
+        info!("{}", total.summary("total"));
+    }
+}
+
+impl Sizes {
+    fn summary(&self, name: &str) -> String {
+        let mut summary = format!(
+            "{}: {} file(s), {}",
+            if name.is_empty() { "(none)" } else { name },
+            self.files,
+            human_size(self.rendered)
+        );
+        if self.written != self.rendered {
+            summary += &format!(", {} minified", human_size(self.written));
+        }
+        for (compression, size) in &self.compressed {
+            summary += &format!(", {} as .{}", human_size(*size), compression.extension());
+        }
+        summary
+    }
+}
+
+fn human_size(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MiB", bytes as f64 / 1024.0 / 1024.0)
+    }
+}
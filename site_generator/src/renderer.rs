@@ -1,62 +1,146 @@
-use std::{fs, fs::File, io::Write, path::Path};
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, HashMap},
+    fs,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
 
-use log::info;
+use log::{error, info};
 use tera::{Context, Tera};
 
-use crate::{markdown::Markdown, model::*};
+use crate::{
+    assets::{plain_path, Assets},
+    highlight::HighlightMode,
+    markdown::{Markdown, MarkdownRenderer, RenderedMarkdown},
+    model::*,
+    slug::UrlStyle,
+};
 
 pub struct Renderer {
     tera: Tera,
+    url_style: UrlStyle,
+    markdown: MarkdownRenderer,
+    /// Root of the output, assets of documents are copied into it
+    output: PathBuf,
+    error_count: Cell<usize>,
+    /// Urls of static files by their urls before fingerprinting, hard coded ones are rewritten in pages
+    asset_urls: Vec<(String, String)>,
 }
 
 impl Renderer {
-    pub fn load_from_path(templates: &str) -> Self {
-        let mut tera = match Tera::new(templates.trim_start_matches("./")) {
-            Ok(t) => t,
-            Err(e) => {
-                panic!("Parsing error(s): {}", e);
-            }
-        };
+    /// `templates` are the content of templates by name.
+    pub fn new(templates: &BTreeMap<String, String>) -> Self {
+        let mut tera = Tera::default();
+        if let Err(e) = tera.add_raw_templates(templates) {
+            panic!("Parsing error(s): {}", e);
+        }
         tera.autoescape_on(vec![]);
-        Renderer { tera }
+        Renderer {
+            tera,
+            url_style: UrlStyle::default(),
+            markdown: MarkdownRenderer::default(),
+            output: PathBuf::new(),
+            error_count: Cell::new(0),
+            asset_urls: Vec::new(),
+        }
     }
 }
 
 impl Renderer {
-    fn render_page(&self, context: &Context, markdown: &Markdown, path: impl AsRef<Path>) {
+    /// Write a page into `dir`, `page` is relative to `dir`.
+    fn write_page(&self, dir: impl AsRef<Path>, page: &str, content: &str) {
+        let path = dir.as_ref().join(self.url_style.file(page));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut content = content.to_string();
+        for (plain, processed) in &self.asset_urls {
+            for quote in ['"', '\''] {
+                content = content.replace(
+                    &format!("{}{}{}", quote, plain, quote),
+                    &format!("{}{}{}", quote, processed, quote),
+                );
+            }
+        }
+        let mut file = File::create(path).unwrap();
+        write!(file, "{}", content).unwrap();
+    }
+    /// Render markdown into html, problems are reported with `name`.
+    fn render_markdown(
+        &self,
+        markdown: &Markdown,
+        article: Option<&Article>,
+        language_site: &LanguageSite,
+        name: &str,
+    ) -> RenderedMarkdown {
+        let rendered = self.markdown.render(markdown, article, language_site);
+        for e in &rendered.errors {
+            error!("{}/{}.md: {}", language_site.language, name, e);
+        }
+        self.error_count
+            .set(self.error_count.get() + rendered.errors.len());
+        for asset in &rendered.assets {
+            let target = self.output.join(&asset.path);
+            if !target.exists() {
+                fs::create_dir_all(target.parent().unwrap()).unwrap();
+                fs::copy(&asset.source, target).unwrap();
+            }
+        }
+        rendered
+    }
+    fn render_page(
+        &self,
+        context: &Context,
+        markdown: &Markdown,
+        language_site: &LanguageSite,
+        path: impl AsRef<Path>,
+    ) {
         let mut context = context.clone();
-        context.insert("name", &markdown.name());
-        context.insert("content", &markdown.html());
+        let rendered_markdown =
+            self.render_markdown(markdown, None, language_site, &markdown.filename);
+        context.insert("name", &markdown.name(self.markdown.config()));
+        context.insert("content", &rendered_markdown.html);
+        context.insert("toc", &rendered_markdown.toc);
         let rendered = self.tera.render("page.html", &context).unwrap();
-        let mut file = File::create(path).unwrap();
-        write!(file, "{}", rendered).unwrap();
+        self.write_page(path, &markdown.filename, &rendered);
     }
     fn render_disambiguation(
         &self,
         context: &mut Context,
         disambiguation: &DisambiguationSearchIndex,
+        page: &str,
         path: impl AsRef<Path>,
     ) {
         let mut context = context.clone();
         context.insert("disambiguation", disambiguation);
         let rendered = self.tera.render("disambiguation.html", &context).unwrap();
-        let mut file = File::create(path).unwrap();
-        write!(file, "{}", rendered).unwrap();
+        self.write_page(path, page, &rendered);
     }
-    fn render_article(&self, context: &Context, article: &Article, path: impl AsRef<Path>) {
-        let mut path = path.as_ref().join(&article.content.filename);
-        path.set_extension("html");
+    fn render_article(
+        &self,
+        context: &Context,
+        article: &Article,
+        language_site: &LanguageSite,
+        path: impl AsRef<Path>,
+    ) {
         let mut context = context.clone();
         context.insert("article", &article);
-        context.insert("name", &article.content.name());
-        context.insert("content", &article.content.html());
+        context.insert("extra", &article.extra);
+        let rendered_markdown = self.render_markdown(
+            &article.content,
+            Some(article),
+            language_site,
+            &format!("{}/{}", article.section, article.content.filename),
+        );
+        context.insert("name", &article.name);
+        context.insert("content", &rendered_markdown.html);
+        context.insert("toc", &rendered_markdown.toc);
         context.insert(
             "content_without_title",
-            &article.content.content_without_title(),
+            &rendered_markdown.content_without_title,
         );
         let rendered = self.tera.render("article.html", &context).unwrap();
-        let mut file = File::create(path).unwrap();
-        write!(file, "{}", rendered).unwrap();
+        self.write_page(path, &article.slug, &rendered);
     }
     fn render_sitemap(&self, context: &mut Context, path: impl AsRef<Path>) {
         let sitemap = self.tera.render("sitemap.xml", context).unwrap();
@@ -65,47 +149,55 @@ impl Renderer {
     }
     fn render_language_index(&self, context: &mut Context, path: impl AsRef<Path>) {
         let index = self.tera.render("index.html", context).unwrap();
-        let mut index_file = File::create(path.as_ref()).unwrap();
-        write!(index_file, "{}", index).unwrap();
+        self.write_page(path, "index", &index);
     }
-    fn render_section(&self, context: &mut Context, section: &Section, path: impl AsRef<Path>) {
-        fs::create_dir_all(&path).unwrap();
+    fn render_section(
+        &self,
+        context: &mut Context,
+        section: &Section,
+        language_site: &LanguageSite,
+        path: impl AsRef<Path>,
+    ) {
         let index = self.tera.render("subindex.html", context).unwrap();
-        let mut file = File::create(path.as_ref().join("index.html")).unwrap();
-        write!(file, "{}", index).unwrap();
+        self.write_page(&path, "index", &index);
         for article in &section.articles {
-            self.render_article(context, article, &path)
+            self.render_article(context, article, language_site, &path)
         }
     }
     fn render_language_site(
         &self,
         context: &mut Context,
         language_site: &LanguageSite,
+        config: &SiteConfig,
         path: impl AsRef<Path>,
     ) {
         fs::create_dir_all(path.as_ref()).unwrap();
         context.insert("language_site", &language_site);
         context.insert("article_count", &language_site.article_count());
-        context.insert("site_index", &language_site.collect_search_indexes());
+        context.insert("site_index", &language_site.collect_search_indexes(config));
         context.insert("now", &chrono::Utc::now());
         let articles: Vec<_> = language_site
             .sections
             .values()
-            .map(|section| section.articles.iter())
-            .flatten()
+            .flat_map(|section| section.articles.iter())
+            .filter(|article| !article.metadata.unlisted)
             .collect();
         context.insert("articles", &articles);
         self.render_sitemap(context, path.as_ref().join("sitemap.xml"));
-        self.render_language_index(context, path.as_ref().join("index.html"));
+        self.render_language_index(context, path.as_ref());
         for (section_name, section) in &language_site.sections {
             info!("render section {} ...", section_name);
             context.insert("section", section);
-            self.render_section(context, section, path.as_ref().join(&section.name));
+            self.render_section(
+                context,
+                section,
+                language_site,
+                path.as_ref().join(&section.name),
+            );
         }
-        fs::create_dir_all(path.as_ref().join("disambiguation")).unwrap();
         info!("render disambiguation pages ...");
         for disambiguation in language_site
-            .collect_search_indexes()
+            .collect_search_indexes(config)
             .iter()
             .filter_map(|it| {
                 if let SearchIndex::Disambiguation(it) = it {
@@ -115,55 +207,122 @@ impl Renderer {
                 }
             })
         {
-            let mut filepath = path
-                .as_ref()
-                .join("disambiguation")
-                .join(&disambiguation.name);
-            filepath.set_extension("html");
-            self.render_disambiguation(context, disambiguation, filepath)
+            self.render_disambiguation(
+                context,
+                disambiguation,
+                &config.disambiguation_page(&disambiguation.name),
+                path.as_ref(),
+            )
         }
         info!("render top level files ...");
         for file in &language_site.top_level_articles {
-            self.render_page(
-                context,
-                file,
-                path.as_ref().join(format!("{}.html", file.filename)),
+            self.render_page(context, file, language_site, path.as_ref());
+        }
+    }
+    fn render_redirect(&self, target_url: &str, path: impl AsRef<Path>) {
+        fs::create_dir_all(path.as_ref().parent().unwrap()).unwrap();
+        let mut file = File::create(path).unwrap();
+        write!(
+            file,
+            r#"<!DOCTYPE html>
+<meta charset="utf-8">
+<title>Redirecting to {url}</title>
+<meta http-equiv="refresh" content="0; URL={url}">
+<link rel="canonical" href="{url}">"#,
+            url = target_url
+        )
+        .unwrap();
+    }
+    fn render_redirects(&self, site: &Site, path: impl AsRef<Path>) {
+        let mut redirects: Vec<_> = site
+            .language_sites
+            .values()
+            .flat_map(|language_site| language_site.redirects(self.url_style))
+            .collect();
+        redirects.sort_by(|a, b| a.from.cmp(&b.from));
+        for redirect in &redirects {
+            self.render_redirect(
+                &site.config.page_url(&redirect.to),
+                path.as_ref().join(&redirect.from),
             );
         }
+        for format in &site.config.redirect_files {
+            let mut file = File::create(path.as_ref().join(format.filename())).unwrap();
+            write!(file, "{}", format.render(&redirects, &site.config)).unwrap();
+        }
+    }
+    fn render_root_index(&self, context: &Context, path: impl AsRef<Path>) {
+        let index = self.tera.render("root.html", context).unwrap();
+        let mut file = File::create(path).unwrap();
+        write!(file, "{}", index).unwrap();
     }
-    pub fn render_to(&self, site: Site, path: impl AsRef<Path>) {
+    pub fn render_to(&mut self, site: Site, assets: &Assets, path: impl AsRef<Path>) {
+        self.url_style = site.config.url_style;
+        self.markdown = MarkdownRenderer::new(&site.config);
+        self.output = path.as_ref().to_path_buf();
+        let config = site.config.clone();
+        self.tera
+            .register_function(
+                "page_url",
+                move |args: &HashMap<String, tera::Value>| match args
+                    .get("page")
+                    .and_then(tera::Value::as_str)
+                {
+                    Some(page) => Ok(config.page_url(page).into()),
+                    None => Err("page_url requires a string `page` argument".into()),
+                },
+            );
+        self.asset_urls = assets
+            .manifest()
+            .iter()
+            .map(|(name, path)| (site.config.url(&plain_path(name)), site.config.url(path)))
+            .filter(|(plain, processed)| plain != processed)
+            .collect();
+        let config = site.config.clone();
+        let manifest = assets.manifest();
+        self.tera
+            .register_function(
+                "asset_url",
+                move |args: &HashMap<String, tera::Value>| match args
+                    .get("name")
+                    .and_then(tera::Value::as_str)
+                {
+                    Some(name) => match manifest.get(name) {
+                        Some(path) => Ok(config.url(path).into()),
+                        None => Err(format!("no static file named `{}`", name).into()),
+                    },
+                    None => Err("asset_url requires a string `name` argument".into()),
+                },
+            );
         fs::remove_dir_all(path.as_ref()).unwrap_or(());
         fs::create_dir_all(path.as_ref()).unwrap();
         let mut context = Context::new();
         context.insert("site", &site);
+        context.insert("languages", &site.language_metas());
+        context.insert("default_language", site.default_language());
+        self.render_root_index(&context, path.as_ref().join("index.html"));
         for (language, language_site) in site.language_sites.iter() {
             info!("Render {:?} site ...", language);
+            context.insert("language_meta", &language_site.meta(&site.config));
             self.render_language_site(
                 &mut context,
                 language_site,
+                &site.config,
                 path.as_ref().join(&language_site.language),
             );
         }
-        let mut index = File::create(path.as_ref().join("index.html")).unwrap();
-        let primary_language = if site.language_sites.contains_key("zh") {
-            "zh"
-        } else {
-            site.language_sites.keys().next().unwrap()
-        };
-        write!(
-            index,
-            r#"<!DOCTYPE html>
-<meta charset="utf-8">
-<title>Redirecting to {}/{}/index.html</title>
-<meta http-equiv="refresh" content="0; URL={}/{}/index.html">
-<link rel="canonical" href="{}/{}/index.html">"#,
-            site.config.public_url,
-            primary_language,
-            site.config.public_url,
-            primary_language,
-            site.config.public_url,
-            primary_language
-        )
-        .unwrap();
+        info!("render redirects ...");
+        self.render_redirects(&site, path.as_ref());
+        if site.config.highlight.mode == HighlightMode::Classes {
+            let mut file = File::create(path.as_ref().join("highlight.css")).unwrap();
+            write!(file, "{}", site.config.highlight.stylesheet()).unwrap();
+        }
+        info!("copy static files ...");
+        assets.write_to(path.as_ref());
+        info!("post process output ...");
+        site.config.output.post_process(path.as_ref());
+        if self.error_count.get() != 0 {
+            panic!("{} error(s) found while rendering", self.error_count.get());
+        }
     }
 }
@@ -0,0 +1,228 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::{Deserialize, Serialize};
+
+use crate::config::default_true;
+
+/// Characters which cannot appear verbatim in a path segment.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Length of the md5 suffix Notion appends to exported filenames.
+const HASH_SUFFIX_LEN: usize = 32;
+
+fn default_separator() -> String {
+    "-".to_string()
+}
+
+/// Rules for turning filenames into the slugs used in urls, configured in `[slug]` of `site.toml`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SlugConfig {
+    /// Transliterate non-ASCII characters into ASCII, eg. `启用` into `Qi-Yong`.
+    #[serde(default)]
+    pub transliterate: bool,
+    #[serde(default)]
+    pub lowercase: bool,
+    /// Replaces whitespaces and punctuations.
+    #[serde(default = "default_separator")]
+    pub separator: String,
+    /// Percent-encode non-ASCII characters in generated links.
+    #[serde(default = "default_true")]
+    pub percent_encode: bool,
+}
+
+impl Default for SlugConfig {
+    fn default() -> Self {
+        Self {
+            transliterate: false,
+            lowercase: false,
+            separator: default_separator(),
+            percent_encode: true,
+        }
+    }
+}
+
+impl SlugConfig {
+    pub fn slugify(&self, text: &str) -> String {
+        let text = strip_hash_suffix(text.trim());
+        let text = if self.transliterate {
+            deunicode::deunicode(text)
+        } else {
+            text.to_string()
+        };
+        let text = if self.lowercase {
+            text.to_lowercase()
+        } else {
+            text
+        };
+        let mut result = String::new();
+        let mut pending_separator = false;
+        for c in text.chars() {
+            if c.is_alphanumeric() || c == '_' {
+                if pending_separator && !result.is_empty() {
+                    result += &self.separator;
+                }
+                pending_separator = false;
+                result.push(c);
+            } else {
+                pending_separator = true;
+            }
+        }
+        result
+    }
+
+    /// Encode a path relative to the site root for using in links.
+    pub fn encode_path(&self, path: &str) -> String {
+        path.split('/')
+            .map(|segment| {
+                if self.percent_encode {
+                    utf8_percent_encode(segment, PATH_SEGMENT).to_string()
+                } else {
+                    segment
+                        .chars()
+                        .map(|c| match c {
+                            c if c.is_ascii() => {
+                                utf8_percent_encode(c.encode_utf8(&mut [0; 4]), PATH_SEGMENT)
+                                    .to_string()
+                            }
+                            c => c.to_string(),
+                        })
+                        .collect()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+fn strip_hash_suffix(text: &str) -> &str {
+    match text.rsplit_once(['-', ' ']) {
+        Some((rest, hash))
+            if !rest.is_empty()
+                && hash.len() == HASH_SUFFIX_LEN
+                && hash.chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            rest.trim_end()
+        }
+        _ => text,
+    }
+}
+
+/// How pages are laid out in the output, configured by `url_style` in `site.toml`.
+///
+/// Pages are identified by their path without extension, eg. `zh/what/CIStr` or `zh/how/index`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UrlStyle {
+    /// `zh/what/CIStr.html`
+    #[default]
+    Html,
+    /// `zh/what/CIStr/index.html`, linked as `zh/what/CIStr/`
+    Pretty,
+}
+
+fn is_index(page: &str) -> bool {
+    page == "index" || page.ends_with("/index")
+}
+
+impl UrlStyle {
+    /// Path of the file a page is written to.
+    pub fn file(self, page: &str) -> String {
+        match self {
+            UrlStyle::Pretty if !is_index(page) => format!("{}/index.html", page),
+            _ => format!("{}.html", page),
+        }
+    }
+
+    /// Path used when linking to a page.
+    pub fn link(self, page: &str) -> String {
+        match self {
+            UrlStyle::Html => format!("{}.html", page),
+            UrlStyle::Pretty if is_index(page) => page.trim_end_matches("index").to_string(),
+            UrlStyle::Pretty => format!("{}/", page),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_notion_hash_suffix() {
+        assert_eq!(
+            strip_hash_suffix("Getting Started 0123456789abcdef0123456789abcdef"),
+            "Getting Started"
+        );
+        assert_eq!(
+            strip_hash_suffix("Getting-Started-0123456789abcdef0123456789abcdef"),
+            "Getting-Started"
+        );
+        // Not a hash: too short, not hexadecimal, or nothing before it
+        assert_eq!(strip_hash_suffix("Release 0123abcd"), "Release 0123abcd");
+        assert_eq!(
+            strip_hash_suffix("Notes 0123456789abcdef0123456789abcdeg"),
+            "Notes 0123456789abcdef0123456789abcdeg"
+        );
+        assert_eq!(
+            strip_hash_suffix("0123456789abcdef0123456789abcdef"),
+            "0123456789abcdef0123456789abcdef"
+        );
+    }
+
+    #[test]
+    fn slugify() {
+        let config = SlugConfig::default();
+        assert_eq!(config.slugify(" Hello,  World! "), "Hello-World");
+        assert_eq!(config.slugify("snake_case"), "snake_case");
+        assert_eq!(config.slugify("启用"), "启用");
+        assert_eq!(
+            config.slugify("Getting Started 0123456789abcdef0123456789abcdef"),
+            "Getting-Started"
+        );
+        assert_eq!(config.slugify("?!"), "");
+    }
+
+    #[test]
+    fn slugify_configured() {
+        let config = SlugConfig {
+            transliterate: true,
+            lowercase: true,
+            separator: "_".to_string(),
+            ..SlugConfig::default()
+        };
+        assert_eq!(config.slugify("Hello World"), "hello_world");
+        assert_eq!(config.slugify("启用"), "qi_yong");
+    }
+
+    #[test]
+    fn html_url_style() {
+        assert_eq!(UrlStyle::Html.file("zh/what/CIStr"), "zh/what/CIStr.html");
+        assert_eq!(UrlStyle::Html.link("zh/what/CIStr"), "zh/what/CIStr.html");
+        assert_eq!(UrlStyle::Html.file("zh/how/index"), "zh/how/index.html");
+        assert_eq!(UrlStyle::Html.link("index"), "index.html");
+    }
+
+    #[test]
+    fn pretty_url_style() {
+        let pretty = UrlStyle::Pretty;
+        assert_eq!(pretty.file("zh/what/CIStr"), "zh/what/CIStr/index.html");
+        assert_eq!(pretty.link("zh/what/CIStr"), "zh/what/CIStr/");
+        // Index pages stay where they are
+        assert_eq!(pretty.file("zh/how/index"), "zh/how/index.html");
+        assert_eq!(pretty.link("zh/how/index"), "zh/how/");
+        assert_eq!(pretty.file("index"), "index.html");
+        assert_eq!(pretty.link("index"), "");
+        // Only a whole `index` segment is an index page
+        assert_eq!(pretty.file("zh/how/reindex"), "zh/how/reindex/index.html");
+        assert_eq!(pretty.link("zh/how/reindex"), "zh/how/reindex/");
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use include_dir::{include_dir, Dir};
+
+use crate::assets;
+
+/// The default theme, those of the example site.
+static DEFAULT_TEMPLATES: Dir = include_dir!("$CARGO_MANIFEST_DIR/example/templates");
+static DEFAULT_STATIC: Dir = include_dir!("$CARGO_MANIFEST_DIR/example/static");
+/// Scripts of the default theme, the example scripts with types erased by
+/// `script/compile-default-theme.sh`.
+static DEFAULT_SCRIPTS: Dir = include_dir!("$CARGO_MANIFEST_DIR/default_theme/static");
+const DEFAULT_TRANSLATIONS: [(&str, &str); 2] = [
+    ("en", include_str!("../example/data/en/translation.toml")),
+    ("zh", include_str!("../example/data/zh/translation.toml")),
+];
+
+/// Templates, static files and default translations a site is rendered with.
+///
+/// A theme directory may have `templates/`, `static/` and `translations/<language>.toml`.
+#[derive(Debug, Clone, Default)]
+pub struct Theme {
+    /// Content of templates by name, eg. `article.html`.
+    pub templates: BTreeMap<String, String>,
+    /// Content of static files by path relative to the static folder.
+    pub static_files: BTreeMap<String, Vec<u8>>,
+    /// Translations used when missing in `translation.toml`, by language.
+    pub translations: HashMap<String, toml::value::Table>,
+}
+
+impl Theme {
+    /// The theme compiled into the binary, used when `site.toml` selects none.
+    pub fn embedded() -> Self {
+        let mut static_files = embedded_files(&DEFAULT_STATIC);
+        // So that no TypeScript compiler is needed
+        for (name, content) in embedded_files(&DEFAULT_SCRIPTS) {
+            if let Some(stem) = name.strip_suffix(".js") {
+                static_files.remove(&format!("{}.ts", stem));
+            }
+            static_files.insert(name, content);
+        }
+        Self {
+            templates: embedded_files(&DEFAULT_TEMPLATES)
+                .into_iter()
+                .map(|(name, content)| (name, String::from_utf8(content).unwrap()))
+                .collect(),
+            static_files,
+            translations: DEFAULT_TRANSLATIONS
+                .iter()
+                .map(|(language, content)| (language.to_string(), toml::from_str(content).unwrap()))
+                .collect(),
+        }
+    }
+
+    pub fn load_from_path(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            panic!("theme {} is not a directory", path.display());
+        }
+        let translations = assets::read_files(&path.join("translations"))
+            .into_iter()
+            .filter_map(|(name, content)| {
+                let language = name.strip_suffix(".toml")?.to_string();
+                let content = String::from_utf8(content).unwrap();
+                Some((language, toml::from_str(&content).unwrap()))
+            })
+            .collect();
+        Self {
+            templates: read_templates(&path.join("templates")),
+            static_files: assets::read_files(&path.join("static")),
+            translations,
+        }
+    }
+
+    /// Templates in `dir` replace those with the same name from the theme.
+    ///
+    /// Templates of the theme stay available as `theme/<name>`, to be extended by the replacements.
+    pub fn override_templates(&mut self, dir: impl AsRef<Path>) {
+        let theme_templates: Vec<_> = self
+            .templates
+            .iter()
+            .map(|(name, content)| (format!("theme/{}", name), content.clone()))
+            .collect();
+        self.templates.extend(read_templates(dir.as_ref()));
+        self.templates.extend(theme_templates);
+    }
+
+    /// Files in `dir` replace static files with the same name from the theme.
+    pub fn override_static(&mut self, dir: impl AsRef<Path>) {
+        self.static_files.extend(assets::read_files(dir.as_ref()));
+    }
+}
+
+/// Default `translation.toml` of `language`, the English one for languages without.
+pub fn default_translation(language: &str) -> &'static str {
+    DEFAULT_TRANSLATIONS
+        .iter()
+        .find(|(it, _)| *it == language)
+        .unwrap_or(&DEFAULT_TRANSLATIONS[0])
+        .1
+}
+
+fn embedded_files(dir: &Dir) -> BTreeMap<String, Vec<u8>> {
+    let mut result: BTreeMap<_, _> = dir
+        .files()
+        .map(|it| {
+            (
+                it.path().to_str().unwrap().replace('\\', "/"),
+                it.contents().to_vec(),
+            )
+        })
+        .collect();
+    for dir in dir.dirs() {
+        result.extend(embedded_files(dir));
+    }
+    result
+}
+
+fn read_templates(dir: &Path) -> BTreeMap<String, String> {
+    assets::read_files(dir)
+        .into_iter()
+        .map(|(name, content)| (name, String::from_utf8(content).unwrap()))
+        .collect()
+}
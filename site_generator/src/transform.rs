@@ -0,0 +1,484 @@
+use std::{collections::HashSet, fs, path::PathBuf};
+
+use percent_encoding::percent_decode_str;
+use pulldown_cmark::{
+    escape::{escape_href, escape_html},
+    CodeBlockKind, CowStr, Event, Tag,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::CacheConfig,
+    diagram::DiagramConfig,
+    highlight::HighlightConfig,
+    markdown::{HeadingConfig, Markdown},
+    math,
+    model::{Article, LanguageSite, SiteConfig},
+    slug::SlugConfig,
+};
+
+/// What a transform knows about the document being rendered, and where it reports back.
+pub struct TransformContext<'c> {
+    pub markdown: &'c Markdown,
+    /// `None` for top level pages like `about.md`
+    pub article: Option<&'c Article>,
+    pub language_site: &'c LanguageSite,
+    /// Ids already given to headings of the document
+    pub heading_ids: HashSet<String>,
+    /// `(level, id, html escaped title)` of headings for the table of contents, except the title,
+    /// empty if the article turns it off
+    pub headings: Vec<(u32, String, String)>,
+    /// Files to copy to the output
+    pub assets: Vec<Asset>,
+    /// Problems found while rendering, eg. invalid formulas
+    pub errors: Vec<String>,
+}
+
+/// A step rewriting the events of a document before they are turned into html.
+pub trait MarkdownTransform {
+    fn transform<'a>(
+        &self,
+        events: Vec<Event<'a>>,
+        context: &mut TransformContext,
+    ) -> Vec<Event<'a>>;
+}
+
+/// Transforms which can be enabled in `[[transforms]]` of `site.toml`,
+/// they run in the listed order after the built-in ones.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum TransformConfig {
+    ExternalLinks(ExternalLinks),
+    LazyImages(LazyImages),
+}
+
+impl TransformConfig {
+    pub fn build(&self, public_url: &str) -> Box<dyn MarkdownTransform> {
+        match self {
+            TransformConfig::ExternalLinks(it) => {
+                let mut it = it.clone();
+                it.public_url = public_url.to_string();
+                Box::new(it)
+            }
+            TransformConfig::LazyImages(it) => Box::new(it.clone()),
+        }
+    }
+}
+
+/// Gives each heading an unique id and collects them for the table of contents.
+#[derive(Debug, Clone, Default)]
+pub struct HeadingIds {
+    pub headings: HeadingConfig,
+    pub slug: SlugConfig,
+}
+
+impl MarkdownTransform for HeadingIds {
+    fn transform<'a>(
+        &self,
+        mut events: Vec<Event<'a>>,
+        context: &mut TransformContext,
+    ) -> Vec<Event<'a>> {
+        let mut index = 0;
+        while index < events.len() {
+            if let Event::Start(Tag::Heading(level, explicit_id, classes)) = &events[index] {
+                let level = *level as u32;
+                let explicit_id = explicit_id.map(ToString::to_string);
+                let mut class = String::new();
+                if !classes.is_empty() {
+                    class = format!(" class=\"{}\"", classes.join(" "));
+                }
+                let end = index
+                    + events[index..]
+                        .iter()
+                        .position(|it| matches!(it, Event::End(Tag::Heading(..))))
+                        .unwrap();
+                let text: String = events[index..end]
+                    .iter()
+                    .filter_map(|it| match it {
+                        Event::Text(t) | Event::Code(t) => Some(t.as_ref()),
+                        _ => None,
+                    })
+                    .collect();
+                // Formulas are left out, their placeholders change with the formulas before them
+                let base_id = explicit_id.unwrap_or_else(|| {
+                    let text = math::strip_placeholders(&text);
+                    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    self.headings.id(&text, &self.slug)
+                });
+                let mut id = base_id.clone();
+                let mut suffix = 1;
+                while !context.heading_ids.insert(id.clone()) {
+                    id = format!("{}-{}", base_id, suffix);
+                    suffix += 1;
+                }
+                events[index] =
+                    Event::Html(CowStr::from(format!("<h{} id=\"{}\"{}>", level, id, class)));
+                events[end] = Event::Html(CowStr::from(if self.headings.anchors {
+                    format!(
+                        "<a class=\"heading-anchor\" href=\"#{}\" aria-hidden=\"true\">{}</a></h{}>\n",
+                        id, self.headings.anchor_symbol, level
+                    )
+                } else {
+                    format!("</h{}>\n", level)
+                }));
+                if level > 1 && context.article.is_none_or(|it| it.metadata.toc) {
+                    let mut title = String::new();
+                    escape_html(&mut title, &text).unwrap();
+                    context.headings.push((level, id, title));
+                }
+                index = end;
+            }
+            index += 1;
+        }
+        events
+    }
+}
+
+/// Highlights fenced code blocks and turns diagrams into svg.
+#[derive(Debug, Clone, Default)]
+pub struct CodeBlocks {
+    pub highlight: HighlightConfig,
+    pub diagrams: DiagramConfig,
+    pub cache: CacheConfig,
+}
+
+impl MarkdownTransform for CodeBlocks {
+    fn transform<'a>(
+        &self,
+        events: Vec<Event<'a>>,
+        context: &mut TransformContext,
+    ) -> Vec<Event<'a>> {
+        let mut result = Vec::with_capacity(events.len());
+        let mut code_block: Option<(String, usize)> = None;
+        for event in events {
+            match (&mut code_block, &event) {
+                (None, Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))) => {
+                    let language = info.split_whitespace().next().unwrap_or("").to_string();
+                    code_block = Some((language, result.len()));
+                    result.push(event);
+                }
+                (Some((language, start)), Event::End(Tag::CodeBlock(_))) => {
+                    let code: String = result[*start..]
+                        .iter()
+                        .filter_map(|it| match it {
+                            Event::Text(t) => Some(t.as_ref()),
+                            _ => None,
+                        })
+                        .collect();
+                    let html =
+                        match self
+                            .diagrams
+                            .render(&code, language, &self.cache.folder("diagrams"))
+                        {
+                            Some(Ok(svg)) => Some(svg),
+                            Some(Err(e)) => {
+                                context.errors.push(e);
+                                None
+                            }
+                            None => self.highlight.highlight(&code, language),
+                        };
+                    if let Some(html) = html {
+                        result.truncate(*start);
+                        result.push(Event::Html(CowStr::from(html)));
+                    } else {
+                        result.push(event);
+                    }
+                    code_block = None;
+                }
+                _ => result.push(event),
+            }
+        }
+        result
+    }
+}
+
+fn default_rel() -> String {
+    "noopener noreferrer".to_string()
+}
+
+/// Adds `rel` and `target` to links leaving the site.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExternalLinks {
+    #[serde(default = "default_rel")]
+    pub rel: String,
+    /// eg. `_blank` to open external links in new tabs
+    #[serde(default)]
+    pub target: Option<String>,
+    #[serde(skip)]
+    public_url: String,
+}
+
+impl ExternalLinks {
+    /// Whether `url` points into the site itself, not just to a host sharing its prefix.
+    fn is_internal(&self, url: &str) -> bool {
+        let base = self.public_url.trim_end_matches('/');
+        url.strip_prefix(base)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?', '#']))
+    }
+}
+
+impl MarkdownTransform for ExternalLinks {
+    fn transform<'a>(
+        &self,
+        events: Vec<Event<'a>>,
+        context: &mut TransformContext,
+    ) -> Vec<Event<'a>> {
+        // Tells screen reader users about the new tab, eg. `（在新标签页打开）`
+        let hint = match self.target {
+            Some(_) => context.language_site.translated("opens_in_new_tab"),
+            None => None,
+        };
+        let mut in_external_link = false;
+        events
+            .into_iter()
+            .map(|event| match &event {
+                Event::Start(Tag::Link(_, url, title))
+                    if (url.starts_with("http://") || url.starts_with("https://"))
+                        && !self.is_internal(url) =>
+                {
+                    in_external_link = true;
+                    let mut html = String::from("<a href=\"");
+                    escape_href(&mut html, url).unwrap();
+                    if !title.is_empty() {
+                        html += "\" title=\"";
+                        escape_html(&mut html, title).unwrap();
+                    }
+                    html += "\" rel=\"";
+                    escape_html(&mut html, &self.rel).unwrap();
+                    if let Some(target) = &self.target {
+                        html += "\" target=\"";
+                        escape_html(&mut html, target).unwrap();
+                    }
+                    html += "\">";
+                    Event::Html(CowStr::from(html))
+                }
+                Event::End(Tag::Link(..)) if in_external_link => {
+                    in_external_link = false;
+                    match hint {
+                        Some(hint) => {
+                            let mut html = String::from("<span class=\"external-link-hint\">");
+                            escape_html(&mut html, hint).unwrap();
+                            html += "</span></a>";
+                            Event::Html(CowStr::from(html))
+                        }
+                        None => event,
+                    }
+                }
+                _ => event,
+            })
+            .collect()
+    }
+}
+
+/// Replace each image, from its start to its end event, by what `f` returns for its url,
+/// title and alt text.
+fn map_images<'a>(
+    events: Vec<Event<'a>>,
+    mut f: impl FnMut(&str, &str, &str) -> Event<'a>,
+) -> Vec<Event<'a>> {
+    let mut result = Vec::with_capacity(events.len());
+    let mut image: Option<(CowStr, CowStr, String)> = None;
+    for event in events {
+        match (&mut image, event) {
+            (None, Event::Start(Tag::Image(_, url, title))) => {
+                image = Some((url, title, String::new()));
+            }
+            (Some((_, _, alt)), Event::Text(text) | Event::Code(text)) => {
+                *alt += &text;
+            }
+            (Some((url, title, alt)), Event::End(Tag::Image(..))) => {
+                result.push(f(url, title, alt));
+                image = None;
+            }
+            (Some(_), _) => {}
+            (None, event) => result.push(event),
+        }
+    }
+    result
+}
+
+/// An `<img>` tag, `attributes` are appended verbatim.
+fn image_html(url: &str, title: &str, alt: &str, attributes: &str) -> String {
+    let mut html = String::from("<img src=\"");
+    escape_href(&mut html, url).unwrap();
+    html += "\" alt=\"";
+    escape_html(&mut html, alt).unwrap();
+    if !title.is_empty() {
+        html += "\" title=\"";
+        escape_html(&mut html, title).unwrap();
+    }
+    html += "\"";
+    html += attributes;
+    html += " />";
+    html
+}
+
+/// A file referenced by a document which is copied to the output.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Asset {
+    pub source: PathBuf,
+    /// Relative to the output root, eg. `assets/diagram.0123456789abcdef.png`
+    pub path: String,
+}
+
+/// Copies images stored next to the markdown files to `assets/` with content hashed names,
+/// together with their resized variants if `[images]` of `site.toml` asks for them.
+#[derive(Debug, Clone)]
+pub struct ColocatedAssets {
+    pub config: SiteConfig,
+}
+
+impl ColocatedAssets {
+    /// Copy the image `url` refers to and return its html, `None` if it is not relative to
+    /// the document.
+    fn image(
+        &self,
+        url: &str,
+        title: &str,
+        alt: &str,
+        context: &mut TransformContext,
+    ) -> Option<Result<String, String>> {
+        if url.is_empty() || url.starts_with('/') || url.starts_with('#') || url.contains(':') {
+            return None;
+        }
+        let relative = percent_decode_str(url).decode_utf8_lossy();
+        let source = match context.markdown.dir.join(relative.as_ref()).canonicalize() {
+            Ok(source) => source,
+            Err(e) => return Some(Err(format!("cannot read image {}: {}", url, e))),
+        };
+        if !self
+            .config
+            .input
+            .canonicalize()
+            .is_ok_and(|input| source.starts_with(input))
+        {
+            return Some(Err(format!("image {} is outside of the input folder", url)));
+        }
+        let content = match fs::read(&source) {
+            Ok(content) => content,
+            Err(e) => return Some(Err(format!("cannot read image {}: {}", url, e))),
+        };
+        let hash = format!("{:x}", Sha256::digest(&content));
+        let stem = source.file_stem().unwrap().to_string_lossy();
+        let name = match source.extension() {
+            Some(extension) => format!("{}.{}.{}", stem, &hash[..16], extension.to_string_lossy()),
+            None => format!("{}.{}", stem, &hash[..16]),
+        };
+        let mut attributes = String::new();
+        if let Ok(size) = imagesize::blob_size(&content) {
+            attributes = format!(" width=\"{}\" height=\"{}\"", size.width, size.height);
+        }
+        let sources = match self.config.images.process(
+            &source,
+            &content,
+            &name,
+            &self.config.cache.folder("images"),
+        ) {
+            Some(Ok(sources)) => sources,
+            Some(Err(e)) => return Some(Err(e)),
+            None => {
+                let path = format!("assets/{}", name);
+                let html = image_html(&self.config.url(&path), title, alt, &attributes);
+                context.assets.push(Asset { source, path });
+                return Some(Ok(html));
+            }
+        };
+        let mut html = String::from("<picture>");
+        for source in sources {
+            let mut srcset = Vec::new();
+            for (width, file, name) in source.variants {
+                let path = format!("assets/{}", name);
+                srcset.push(format!("{} {}w", self.config.url(&path), width));
+                context.assets.push(Asset { source: file, path });
+            }
+            let srcset = srcset.join(", ");
+            let sizes = &self.config.images.sizes;
+            match source.mime {
+                Some(mime) => {
+                    html += &format!(
+                        "<source type=\"{}\" srcset=\"{}\" sizes=\"{}\">",
+                        mime, srcset, sizes
+                    )
+                }
+                None => {
+                    html += &image_html(
+                        &self.config.url(&format!("assets/{}", name)),
+                        title,
+                        alt,
+                        &format!("{} srcset=\"{}\" sizes=\"{}\"", attributes, srcset, sizes),
+                    )
+                }
+            }
+        }
+        html += "</picture>";
+        Some(Ok(html))
+    }
+}
+
+impl MarkdownTransform for ColocatedAssets {
+    fn transform<'a>(
+        &self,
+        events: Vec<Event<'a>>,
+        context: &mut TransformContext,
+    ) -> Vec<Event<'a>> {
+        map_images(events, |url, title, alt| {
+            let html = match self.image(url, title, alt, context) {
+                Some(Ok(html)) => html,
+                Some(Err(e)) => {
+                    context.errors.push(e);
+                    image_html(url, title, alt, "")
+                }
+                None => image_html(url, title, alt, ""),
+            };
+            Event::Html(CowStr::from(html))
+        })
+    }
+}
+
+const LAZY: &str = " loading=\"lazy\" decoding=\"async\"";
+
+/// Lets browsers defer loading images until they are scrolled to.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct LazyImages {}
+
+impl MarkdownTransform for LazyImages {
+    fn transform<'a>(
+        &self,
+        events: Vec<Event<'a>>,
+        _context: &mut TransformContext,
+    ) -> Vec<Event<'a>> {
+        map_images(events, |url, title, alt| {
+            Event::Html(CowStr::from(image_html(url, title, alt, LAZY)))
+        })
+        .into_iter()
+        .map(|event| match event {
+            // Images turned into html by earlier transforms
+            Event::Html(html) if html.contains("<img ") && !html.contains(" loading=") => {
+                Event::Html(CowStr::from(html.replace(" />", &format!("{} />", LAZY))))
+            }
+            event => event,
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_links() {
+        let links = ExternalLinks {
+            rel: default_rel(),
+            target: None,
+            public_url: "http://localhost:5000".to_string(),
+        };
+        assert!(links.is_internal("http://localhost:5000"));
+        assert!(links.is_internal("http://localhost:5000/en/index.html"));
+        assert!(links.is_internal("http://localhost:5000?q=1"));
+        assert!(!links.is_internal("http://localhost:50001/en/index.html"));
+        assert!(!links.is_internal("http://localhost:5000.example.com/"));
+        assert!(!links.is_internal("https://example.com/"));
+    }
+}